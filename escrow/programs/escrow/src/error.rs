@@ -0,0 +1,57 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum EscrowError {
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Offer has expired")]
+    OfferExpired,
+    #[msg("Offer has not expired yet")]
+    OfferNotExpired,
+    #[msg("Invalid offer amounts")]
+    InvalidAmount,
+    #[msg("Fill is smaller than the maker's minimum")]
+    FillTooSmall,
+    #[msg("Taker is not allowed to take this offer")]
+    TakerNotAllowed,
+    #[msg("Bundle legs must be non-empty, bounded, non-zero and unique")]
+    InvalidBundle,
+    #[msg("Leg accounts do not match the bundle")]
+    InvalidLegAccounts,
+    #[msg("Token accounts are required for a token leg")]
+    MissingTokenAccount,
+    #[msg("Token accounts must be omitted for a native SOL leg")]
+    UnexpectedTokenAccount,
+    #[msg("Fee exceeds the protocol cap")]
+    FeeTooHigh,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Escrow is not in a state that allows this")]
+    InvalidStatus,
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+    #[msg("Dispute window is still open")]
+    DisputeWindowOpen,
+    #[msg("Arbiter must be neither the buyer nor the seller")]
+    InvalidArbiter,
+    #[msg("Milestones must be non-empty, bounded, non-zero and in order of future deadlines")]
+    InvalidMilestones,
+    #[msg("Milestone deadline has passed")]
+    MilestoneOverdue,
+    #[msg("Final milestone deadline has not passed yet")]
+    MilestonesPending,
+    #[msg("Oracle account is not a valid price feed for this escrow")]
+    InvalidOracle,
+    #[msg("Oracle price is stale")]
+    OracleStale,
+    #[msg("Oracle price is outside the maker's bounds")]
+    OraclePriceOutOfBounds,
+    #[msg("Fill costs more than the taker's limit")]
+    SlippageExceeded,
+    #[msg("Withdrawal would leave the treasury below rent exemption")]
+    BelowRentExemption,
+    #[msg("The arbiter still has time to resolve the dispute")]
+    ArbitrationPending,
+    #[msg("Oracle confidence interval is wider than the maker allows")]
+    OracleConfidenceTooWide,
+}
@@ -0,0 +1,340 @@
+use crate::error::EscrowError;
+use crate::state::{ArbitratedEscrow, DealStatus};
+use crate::transfer::{harvest_withheld_fees, transfer_checked};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct OpenArbitrated<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = buyer,
+        seeds = [b"arbitrated", buyer.key().as_ref(), seed.to_le_bytes().as_ref()],
+        space = 8 + ArbitratedEscrow::INIT_SPACE,
+        bump
+    )]
+    pub arbitrated_escrow: Account<'info, ArbitratedEscrow>,
+    #[account(
+        init,
+        payer = buyer,
+        associated_token::mint = mint,
+        associated_token::authority = arbitrated_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateArbitrated<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"arbitrated",
+            arbitrated_escrow.buyer.as_ref(),
+            arbitrated_escrow.seed.to_le_bytes().as_ref()
+        ],
+        bump = arbitrated_escrow.bump
+    )]
+    pub arbitrated_escrow: Account<'info, ArbitratedEscrow>,
+}
+
+#[derive(Accounts)]
+pub struct SettleArbitrated<'info> {
+    /// The buyer, the arbiter, or anyone once the dispute window lapses
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    /// Receives the escrow's rent, having paid it
+    #[account(mut)]
+    pub buyer: SystemAccount<'info>,
+    pub seller: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Omitted when the buyer is paid nothing
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Omitted when the seller is paid nothing
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program
+    )]
+    pub seller_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        close = buyer,
+        has_one = buyer,
+        has_one = seller,
+        has_one = mint,
+        seeds = [b"arbitrated", buyer.key().as_ref(), arbitrated_escrow.seed.to_le_bytes().as_ref()],
+        bump = arbitrated_escrow.bump
+    )]
+    pub arbitrated_escrow: Box<Account<'info, ArbitratedEscrow>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = arbitrated_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> OpenArbitrated<'info> {
+    pub fn open_arbitrated(
+        &mut self,
+        seed: u64,
+        seller: Pubkey,
+        arbiter: Pubkey,
+        dispute_window: i64,
+        bumps: &OpenArbitratedBumps,
+    ) -> Result<()> {
+        require!(dispute_window > 0, EscrowError::InvalidAmount);
+        require!(
+            arbiter != self.buyer.key() && arbiter != seller,
+            EscrowError::InvalidArbiter
+        );
+
+        self.arbitrated_escrow.set_inner(ArbitratedEscrow {
+            seed,
+            buyer: self.buyer.key(),
+            seller,
+            arbiter,
+            mint: self.mint.key(),
+            dispute_window,
+            status: DealStatus::Funded,
+            delivered_at: 0,
+            disputed_at: 0,
+            bump: bumps.arbitrated_escrow,
+        });
+        Ok(())
+    }
+
+    pub fn fund(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(amount > 0, EscrowError::InvalidAmount);
+
+        let transfer_accounts = TransferChecked {
+            from: self.buyer_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.buyer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+impl<'info> UpdateArbitrated<'info> {
+    /// Starts the dispute window; only the seller can mark delivery.
+    pub fn mark_delivered(&mut self) -> Result<()> {
+        require_keys_eq!(
+            self.signer.key(),
+            self.arbitrated_escrow.seller,
+            EscrowError::Unauthorized
+        );
+        require!(
+            self.arbitrated_escrow.status == DealStatus::Funded,
+            EscrowError::InvalidStatus
+        );
+
+        self.arbitrated_escrow.status = DealStatus::Delivered;
+        self.arbitrated_escrow.delivered_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Hands the deal to the arbiter, who has `ARBITRATION_PERIOD` to settle it.
+    pub fn open_dispute(&mut self) -> Result<()> {
+        require_keys_eq!(
+            self.signer.key(),
+            self.arbitrated_escrow.buyer,
+            EscrowError::Unauthorized
+        );
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            self.arbitrated_escrow.can_dispute(now),
+            EscrowError::DisputeWindowClosed
+        );
+
+        self.arbitrated_escrow.status = DealStatus::Disputed;
+        self.arbitrated_escrow.disputed_at = now;
+        Ok(())
+    }
+}
+
+impl<'info> SettleArbitrated<'info> {
+    /// The buyer releases everything to the seller, before or after delivery.
+    pub fn confirm_delivery(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require_keys_eq!(
+            self.signer.key(),
+            self.arbitrated_escrow.buyer,
+            EscrowError::Unauthorized
+        );
+        require!(
+            self.arbitrated_escrow.status != DealStatus::Disputed,
+            EscrowError::InvalidStatus
+        );
+
+        self.pay_out(self.vault.amount, remaining_accounts)
+    }
+
+    /// Anyone may release undisputed funds to the seller once the window lapses.
+    pub fn release_after_window(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(
+            self.arbitrated_escrow.status == DealStatus::Delivered,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            !self
+                .arbitrated_escrow
+                .can_dispute(Clock::get()?.unix_timestamp),
+            EscrowError::DisputeWindowOpen
+        );
+
+        self.pay_out(self.vault.amount, remaining_accounts)
+    }
+
+    /// The arbiter sends `seller_amount` to the seller and the rest back to
+    /// the buyer.
+    pub fn resolve_dispute(
+        &mut self,
+        seller_amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require_keys_eq!(
+            self.signer.key(),
+            self.arbitrated_escrow.arbiter,
+            EscrowError::Unauthorized
+        );
+        require!(
+            self.arbitrated_escrow.status == DealStatus::Disputed,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            seller_amount <= self.vault.amount,
+            EscrowError::InvalidAmount
+        );
+
+        self.pay_out(seller_amount, remaining_accounts)
+    }
+
+    /// The buyer takes everything back from a dispute the arbiter never settled.
+    pub fn refund_unresolved(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require_keys_eq!(
+            self.signer.key(),
+            self.arbitrated_escrow.buyer,
+            EscrowError::Unauthorized
+        );
+        require!(
+            self.arbitrated_escrow.status == DealStatus::Disputed,
+            EscrowError::InvalidStatus
+        );
+        require!(
+            self.arbitrated_escrow
+                .arbitration_lapsed(Clock::get()?.unix_timestamp),
+            EscrowError::ArbitrationPending
+        );
+
+        self.pay_out(0, remaining_accounts)
+    }
+
+    fn pay_out(
+        &mut self,
+        seller_amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let buyer_key = self.buyer.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"arbitrated",
+            buyer_key.as_ref(),
+            &self.arbitrated_escrow.seed.to_le_bytes()[..],
+            &[self.arbitrated_escrow.bump],
+        ]];
+
+        let buyer_amount = self.vault.amount - seller_amount;
+        for (to, amount) in [
+            (&self.seller_ata, seller_amount),
+            (&self.buyer_ata, buyer_amount),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            let Some(to) = to else {
+                return err!(EscrowError::MissingTokenAccount);
+            };
+
+            let transfer_accounts = TransferChecked {
+                from: self.vault.to_account_info(),
+                mint: self.mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: self.arbitrated_escrow.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                transfer_accounts,
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+        }
+
+        // Close the vault account
+        harvest_withheld_fees(
+            &self.token_program.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault.to_account_info(),
+        )?;
+        let close_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.buyer.to_account_info(),
+            authority: self.arbitrated_escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)
+    }
+}
@@ -0,0 +1,384 @@
+use crate::error::EscrowError;
+use crate::state::{Bundle, BundleLeg};
+use crate::transfer::{harvest_withheld_fees, transfer_checked};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{
+        create_idempotent, get_associated_token_address_with_program_id, AssociatedToken, Create,
+    },
+    token::Token,
+    token_2022::Token2022,
+    token_interface::{close_account, CloseAccount, Mint, TokenAccount, TransferChecked},
+};
+
+/// Accounts passed per leg in `remaining_accounts`, ahead of any
+/// transfer-hook extra accounts
+const LEG_ACCOUNTS: usize = 3;
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct MakeBundle<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"bundle", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        space = 8 + Bundle::INIT_SPACE,
+        bump
+    )]
+    pub bundle: Account<'info, Bundle>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// Legs may mix SPL Token and Token-2022 mints; each uses its mint's owner
+    pub token_program: Program<'info, Token>,
+    pub token_2022_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TakeBundle<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bump
+    )]
+    pub bundle: Account<'info, Bundle>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// Legs may mix SPL Token and Token-2022 mints; each uses its mint's owner
+    pub token_program: Program<'info, Token>,
+    pub token_2022_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundBundle<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        seeds = [b"bundle", maker.key().as_ref(), bundle.seed.to_le_bytes().as_ref()],
+        bump = bundle.bump
+    )]
+    pub bundle: Account<'info, Bundle>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// Legs may mix SPL Token and Token-2022 mints; each uses its mint's owner
+    pub token_program: Program<'info, Token>,
+    pub token_2022_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeBundle<'info> {
+    pub fn make_bundle(
+        &mut self,
+        seed: u64,
+        offered: Vec<BundleLeg>,
+        requested: Vec<BundleLeg>,
+        remaining_accounts: &[AccountInfo<'info>],
+        bumps: &MakeBundleBumps,
+    ) -> Result<()> {
+        require!(
+            Bundle::valid_legs(&offered) && Bundle::valid_legs(&requested),
+            EscrowError::InvalidBundle
+        );
+        require!(
+            remaining_accounts.len() >= offered.len() * LEG_ACCOUNTS,
+            EscrowError::InvalidLegAccounts
+        );
+        let (leg_accounts, hook_accounts) =
+            remaining_accounts.split_at(offered.len() * LEG_ACCOUNTS);
+
+        let maker_key = self.maker.key();
+        let bundle_key = self.bundle.key();
+        for (leg, accounts) in offered.iter().zip(leg_accounts.chunks(LEG_ACCOUNTS)) {
+            let (mint, maker_ata, vault) = (&accounts[0], &accounts[1], &accounts[2]);
+            let token_program =
+                leg_token_program(mint, &self.token_program, &self.token_2022_program)?;
+            let decimals = check_mint(mint, leg)?;
+            check_ata(maker_ata, &maker_key, &leg.mint, token_program.key)?;
+            check_ata(vault, &bundle_key, &leg.mint, token_program.key)?;
+
+            create_ata(
+                &self.associated_token_program,
+                &token_program,
+                &self.system_program,
+                self.maker.to_account_info(),
+                vault,
+                self.bundle.to_account_info(),
+                mint,
+            )?;
+
+            let transfer_accounts = TransferChecked {
+                from: maker_ata.clone(),
+                mint: mint.clone(),
+                to: vault.clone(),
+                authority: self.maker.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(token_program, transfer_accounts)
+                .with_remaining_accounts(hook_accounts.to_vec());
+            transfer_checked(cpi_ctx, leg.amount, decimals)?;
+        }
+
+        self.bundle.set_inner(Bundle {
+            seed,
+            maker: maker_key,
+            offered,
+            requested,
+            bump: bumps.bundle,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> TakeBundle<'info> {
+    /// Pays every requested leg to the maker, then releases every offered
+    /// leg to the taker; any missing or mismatched leg fails the whole take.
+    pub fn take_bundle(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let requested_len = self.bundle.requested.len() * LEG_ACCOUNTS;
+        let legs_len = requested_len + self.bundle.offered.len() * LEG_ACCOUNTS;
+        require!(
+            remaining_accounts.len() >= legs_len,
+            EscrowError::InvalidLegAccounts
+        );
+        let (leg_accounts, hook_accounts) = remaining_accounts.split_at(legs_len);
+        let (requested_accounts, offered_accounts) = leg_accounts.split_at(requested_len);
+
+        let maker_key = self.maker.key();
+        let taker_key = self.taker.key();
+        let bundle_key = self.bundle.key();
+
+        for (leg, accounts) in self
+            .bundle
+            .requested
+            .iter()
+            .zip(requested_accounts.chunks(LEG_ACCOUNTS))
+        {
+            let (mint, taker_ata, maker_ata) = (&accounts[0], &accounts[1], &accounts[2]);
+            let token_program =
+                leg_token_program(mint, &self.token_program, &self.token_2022_program)?;
+            let decimals = check_mint(mint, leg)?;
+            check_ata(taker_ata, &taker_key, &leg.mint, token_program.key)?;
+            check_ata(maker_ata, &maker_key, &leg.mint, token_program.key)?;
+            create_ata(
+                &self.associated_token_program,
+                &token_program,
+                &self.system_program,
+                self.taker.to_account_info(),
+                maker_ata,
+                self.maker.to_account_info(),
+                mint,
+            )?;
+
+            let transfer_accounts = TransferChecked {
+                from: taker_ata.clone(),
+                mint: mint.clone(),
+                to: maker_ata.clone(),
+                authority: self.taker.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(token_program, transfer_accounts)
+                .with_remaining_accounts(hook_accounts.to_vec());
+            transfer_checked(cpi_ctx, leg.amount, decimals)?;
+        }
+
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"bundle",
+            maker_key.as_ref(),
+            &self.bundle.seed.to_le_bytes()[..],
+            &[self.bundle.bump],
+        ]];
+        for (leg, accounts) in self
+            .bundle
+            .offered
+            .iter()
+            .zip(offered_accounts.chunks(LEG_ACCOUNTS))
+        {
+            let (mint, vault, taker_ata) = (&accounts[0], &accounts[1], &accounts[2]);
+            let token_program =
+                leg_token_program(mint, &self.token_program, &self.token_2022_program)?;
+            check_mint(mint, leg)?;
+            check_ata(vault, &bundle_key, &leg.mint, token_program.key)?;
+            check_ata(taker_ata, &taker_key, &leg.mint, token_program.key)?;
+            create_ata(
+                &self.associated_token_program,
+                &token_program,
+                &self.system_program,
+                self.taker.to_account_info(),
+                taker_ata,
+                self.taker.to_account_info(),
+                mint,
+            )?;
+
+            release_leg(
+                token_program,
+                mint,
+                vault,
+                taker_ata,
+                self.bundle.to_account_info(),
+                self.maker.to_account_info(),
+                hook_accounts,
+                signer_seeds,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'info> RefundBundle<'info> {
+    pub fn refund_bundle(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let legs_len = self.bundle.offered.len() * LEG_ACCOUNTS;
+        require!(
+            remaining_accounts.len() >= legs_len,
+            EscrowError::InvalidLegAccounts
+        );
+        let (leg_accounts, hook_accounts) = remaining_accounts.split_at(legs_len);
+
+        let maker_key = self.maker.key();
+        let bundle_key = self.bundle.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"bundle",
+            maker_key.as_ref(),
+            &self.bundle.seed.to_le_bytes()[..],
+            &[self.bundle.bump],
+        ]];
+
+        for (leg, accounts) in self
+            .bundle
+            .offered
+            .iter()
+            .zip(leg_accounts.chunks(LEG_ACCOUNTS))
+        {
+            let (mint, vault, maker_ata) = (&accounts[0], &accounts[1], &accounts[2]);
+            let token_program =
+                leg_token_program(mint, &self.token_program, &self.token_2022_program)?;
+            check_mint(mint, leg)?;
+            check_ata(vault, &bundle_key, &leg.mint, token_program.key)?;
+            check_ata(maker_ata, &maker_key, &leg.mint, token_program.key)?;
+
+            create_ata(
+                &self.associated_token_program,
+                &token_program,
+                &self.system_program,
+                self.maker.to_account_info(),
+                maker_ata,
+                self.maker.to_account_info(),
+                mint,
+            )?;
+
+            release_leg(
+                token_program,
+                mint,
+                vault,
+                maker_ata,
+                self.bundle.to_account_info(),
+                self.maker.to_account_info(),
+                hook_accounts,
+                signer_seeds,
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The program that owns `mint`, which must be SPL Token or Token-2022.
+fn leg_token_program<'info>(
+    mint: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    token_2022_program: &Program<'info, Token2022>,
+) -> Result<AccountInfo<'info>> {
+    if *mint.owner == token_program.key() {
+        Ok(token_program.to_account_info())
+    } else if *mint.owner == token_2022_program.key() {
+        Ok(token_2022_program.to_account_info())
+    } else {
+        err!(EscrowError::InvalidLegAccounts)
+    }
+}
+
+/// Checks `mint` is the leg's mint, returning its decimals.
+fn check_mint(mint: &AccountInfo, leg: &BundleLeg) -> Result<u8> {
+    require_keys_eq!(mint.key(), leg.mint, EscrowError::InvalidLegAccounts);
+    Ok(Mint::try_deserialize(&mut &mint.try_borrow_data()?[..])?.decimals)
+}
+
+fn check_ata(
+    ata: &AccountInfo,
+    authority: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<()> {
+    require_keys_eq!(
+        ata.key(),
+        get_associated_token_address_with_program_id(authority, mint, token_program),
+        EscrowError::InvalidLegAccounts
+    );
+    Ok(())
+}
+
+/// Creates the `authority` ATA for `mint` unless it already exists.
+fn create_ata<'info>(
+    associated_token_program: &Program<'info, AssociatedToken>,
+    token_program: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    payer: AccountInfo<'info>,
+    ata: &AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+) -> Result<()> {
+    create_idempotent(CpiContext::new(
+        associated_token_program.to_account_info(),
+        Create {
+            payer,
+            associated_token: ata.clone(),
+            authority,
+            mint: mint.clone(),
+            system_program: system_program.to_account_info(),
+            token_program: token_program.clone(),
+        },
+    ))
+}
+
+/// Sweeps a bundle vault into `to` and closes it, returning its rent to the
+/// maker. `mint` must be writable in case withheld fees need harvesting.
+#[allow(clippy::too_many_arguments)]
+fn release_leg<'info>(
+    token_program: AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    bundle: AccountInfo<'info>,
+    maker: AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let decimals = Mint::try_deserialize(&mut &mint.try_borrow_data()?[..])?.decimals;
+    let amount = TokenAccount::try_deserialize(&mut &vault.try_borrow_data()?[..])?.amount;
+
+    let transfer_accounts = TransferChecked {
+        from: vault.clone(),
+        mint: mint.clone(),
+        to: to.clone(),
+        authority: bundle.clone(),
+    };
+    let cpi_ctx =
+        CpiContext::new_with_signer(token_program.clone(), transfer_accounts, signer_seeds)
+            .with_remaining_accounts(hook_accounts.to_vec());
+    transfer_checked(cpi_ctx, amount, decimals)?;
+
+    harvest_withheld_fees(&token_program, mint, vault)?;
+    let close_accounts = CloseAccount {
+        account: vault.clone(),
+        destination: maker,
+        authority: bundle,
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, close_accounts, signer_seeds);
+    close_account(cpi_ctx)
+}
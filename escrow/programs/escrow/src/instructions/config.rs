@@ -0,0 +1,179 @@
+use crate::error::EscrowError;
+use crate::program::Escrow;
+use crate::state::EscrowConfig;
+use crate::transfer::transfer_checked;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{
+    token::spl_token::native_mint,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    /// The program's upgrade authority, who becomes the config admin
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"config"],
+        space = 8 + EscrowConfig::INIT_SPACE,
+        bump
+    )]
+    pub config: Account<'info, EscrowConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: SystemAccount<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Escrow>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ EscrowError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        has_one = admin @ EscrowError::Unauthorized,
+        seeds = [b"config"],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, EscrowConfig>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        has_one = admin @ EscrowError::Unauthorized,
+        seeds = [b"config"],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, EscrowConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = config.treasury_bump
+    )]
+    pub treasury: SystemAccount<'info>,
+    /// The native mint withdraws collected lamports to the admin
+    #[account(
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// Omitted when withdrawing native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program
+    )]
+    pub treasury_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Omitted when withdrawing native SOL
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub destination: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitConfig<'info> {
+    pub fn init_config(&mut self, fee_bps: u16, bumps: &InitConfigBumps) -> Result<()> {
+        require!(
+            fee_bps <= EscrowConfig::MAX_FEE_BPS,
+            EscrowError::FeeTooHigh
+        );
+
+        self.config.set_inner(EscrowConfig {
+            admin: self.admin.key(),
+            fee_bps,
+            config_bump: bumps.config,
+            treasury_bump: bumps.treasury,
+        });
+
+        // Fund the treasury up front so fees smaller than its rent can land
+        let rent = Rent::get()?.minimum_balance(0);
+        let shortfall = rent.saturating_sub(self.treasury.lamports());
+        if shortfall > 0 {
+            let cpi_accounts = Transfer {
+                from: self.admin.to_account_info(),
+                to: self.treasury.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, shortfall)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'info> UpdateConfig<'info> {
+    pub fn set_fee(&mut self, fee_bps: u16) -> Result<()> {
+        require!(
+            fee_bps <= EscrowConfig::MAX_FEE_BPS,
+            EscrowError::FeeTooHigh
+        );
+        self.config.fee_bps = fee_bps;
+        Ok(())
+    }
+}
+
+impl<'info> WithdrawTreasury<'info> {
+    pub fn withdraw_treasury(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let signer_seeds: &[&[&[u8]]] = &[&[b"treasury", &[self.config.treasury_bump]]];
+
+        if self.mint.key() == native_mint::ID {
+            // The treasury must stay rent exempt to keep receiving small fees
+            let available = self
+                .treasury
+                .lamports()
+                .saturating_sub(Rent::get()?.minimum_balance(0));
+            require!(amount <= available, EscrowError::BelowRentExemption);
+
+            let cpi_accounts = Transfer {
+                from: self.treasury.to_account_info(),
+                to: self.admin.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                cpi_accounts,
+                signer_seeds,
+            );
+            return transfer(cpi_ctx, amount);
+        }
+
+        let (Some(treasury_ata), Some(destination)) = (&self.treasury_ata, &self.destination)
+        else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+        let transfer_accounts = TransferChecked {
+            from: treasury_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: destination.to_account_info(),
+            authority: self.treasury.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
@@ -0,0 +1,479 @@
+use crate::error::EscrowError;
+use crate::state::{CounterOffer, Escrow, EscrowConfig, OfferBook};
+use crate::transfer::{harvest_withheld_fees, transfer_checked, transfer_fee};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+#[derive(Accounts)]
+pub struct MakeCounterOffer<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    pub maker: SystemAccount<'info>,
+    #[account(
+        mint::token_program = token_program
+    )]
+    pub mint_b: InterfaceAccount<'info, Mint>,
+    /// Omitted when the escrow is paid in native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program
+    )]
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        has_one = maker,
+        has_one = mint_b,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        init,
+        payer = taker,
+        seeds = [b"counter", escrow.key().as_ref(), taker.key().as_ref()],
+        space = 8 + CounterOffer::INIT_SPACE,
+        bump
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+    /// Omitted when the escrow is paid in native SOL, which the counter
+    /// offer holds as lamports
+    #[account(
+        init,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+        associated_token::token_program = token_program
+    )]
+    pub counter_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelCounterOffer<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint_b: InterfaceAccount<'info, Mint>,
+    /// Omitted when the escrow is paid in native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program
+    )]
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = taker,
+        has_one = mint_b,
+        seeds = [b"counter", counter_offer.escrow.as_ref(), taker.key().as_ref()],
+        bump = counter_offer.bump
+    )]
+    pub counter_offer: Account<'info, CounterOffer>,
+    /// Omitted when the escrow is paid in native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+        associated_token::token_program = token_program
+    )]
+    pub counter_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptCounterOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(mut)]
+    pub taker: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program_a
+    )]
+    pub mint_a: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        mint::token_program = token_program_b
+    )]
+    pub mint_b: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        has_one = maker,
+        has_one = mint_a,
+        has_one = mint_b,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Box<Account<'info, Escrow>>,
+    #[account(
+        mut,
+        seeds = [b"offer_book", mint_a.key().as_ref(), mint_b.key().as_ref()],
+        bump = offer_book.bump
+    )]
+    pub offer_book: Box<Account<'info, OfferBook>>,
+    /// Omitted when the escrow sells native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program_a
+    )]
+    pub vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        close = taker,
+        has_one = escrow,
+        has_one = taker,
+        seeds = [b"counter", escrow.key().as_ref(), taker.key().as_ref()],
+        bump = counter_offer.bump
+    )]
+    pub counter_offer: Box<Account<'info, CounterOffer>>,
+    /// Omitted when the escrow is paid in native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_b,
+        associated_token::authority = counter_offer,
+        associated_token::token_program = token_program_b
+    )]
+    pub counter_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Omitted when the escrow is paid in native SOL
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_b,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program_b
+    )]
+    pub maker_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Omitted when the escrow sells native SOL
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_a,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program_a
+    )]
+    pub taker_ata_a: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump
+    )]
+    pub config: Box<Account<'info, EscrowConfig>>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = config.treasury_bump
+    )]
+    pub treasury: SystemAccount<'info>,
+    /// Omitted when the escrow is paid in native SOL or no protocol fee is due
+    #[account(
+        init_if_needed,
+        payer = maker,
+        associated_token::mint = mint_b,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_b
+    )]
+    pub treasury_ata_b: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// Owner of `mint_a`; the native mint belongs to SPL Token
+    pub token_program_a: Interface<'info, TokenInterface>,
+    /// Owner of `mint_b`, which may differ from `mint_a`'s
+    pub token_program_b: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MakeCounterOffer<'info> {
+    pub fn make_counter_offer(
+        &mut self,
+        amount: u64,
+        offer: u64,
+        proof: &[[u8; 32]],
+        remaining_accounts: &[AccountInfo<'info>],
+        bumps: &MakeCounterOfferBumps,
+    ) -> Result<()> {
+        require!(
+            self.escrow.is_allowed_taker(&self.taker.key(), proof),
+            EscrowError::TakerNotAllowed
+        );
+        self.escrow
+            .ensure_fillable(amount, Clock::get()?.unix_timestamp)?;
+        require!(offer > 0, EscrowError::InvalidAmount);
+
+        let offer = if self.escrow.buys_sol() {
+            // A native-mint counter vault would never be swept or closed
+            require!(
+                self.taker_ata_b.is_none() && self.counter_vault.is_none(),
+                EscrowError::UnexpectedTokenAccount
+            );
+            let cpi_accounts = Transfer {
+                from: self.taker.to_account_info(),
+                to: self.counter_offer.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, offer)?;
+            offer
+        } else {
+            let (Some(taker_ata_b), Some(counter_vault)) = (&self.taker_ata_b, &self.counter_vault)
+            else {
+                return err!(EscrowError::MissingTokenAccount);
+            };
+            let transfer_accounts = TransferChecked {
+                from: taker_ata_b.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+                to: counter_vault.to_account_info(),
+                authority: self.taker.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts)
+                .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, offer, self.mint_b.decimals)?;
+            offer - transfer_fee(&self.mint_b.to_account_info(), offer)?
+        };
+
+        self.counter_offer.set_inner(CounterOffer {
+            escrow: self.escrow.key(),
+            taker: self.taker.key(),
+            mint_b: self.mint_b.key(),
+            amount,
+            offer,
+            bump: bumps.counter_offer,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> CancelCounterOffer<'info> {
+    /// Returns the escrowed `mint_b` to the taker; lamports held for a
+    /// native SOL counter offer go back when the account closes.
+    pub fn cancel_counter_offer(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.counter_offer.pays_sol() {
+            return Ok(());
+        }
+        let (Some(taker_ata_b), Some(counter_vault)) = (&self.taker_ata_b, &self.counter_vault)
+        else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+
+        let taker_key = self.taker.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"counter",
+            self.counter_offer.escrow.as_ref(),
+            taker_key.as_ref(),
+            &[self.counter_offer.bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: counter_vault.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            to: taker_ata_b.to_account_info(),
+            authority: self.counter_offer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, counter_vault.amount, self.mint_b.decimals)?;
+        harvest_withheld_fees(
+            &self.token_program.to_account_info(),
+            &self.mint_b.to_account_info(),
+            &counter_vault.to_account_info(),
+        )?;
+
+        let close_accounts = CloseAccount {
+            account: counter_vault.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.counter_offer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)
+    }
+}
+
+impl<'info> AcceptCounterOffer<'info> {
+    /// Pays the counter offer's escrowed `mint_b` to the maker, less the
+    /// protocol fee, which goes to the treasury.
+    pub fn pay_maker(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        self.escrow
+            .ensure_fillable(self.counter_offer.amount, Clock::get()?.unix_timestamp)?;
+
+        if self.escrow.buys_sol() {
+            let offer = self.counter_offer.offer;
+            let protocol_fee = self.config.protocol_fee(offer);
+            self.counter_offer.sub_lamports(offer)?;
+            self.maker.add_lamports(offer - protocol_fee)?;
+            self.treasury.add_lamports(protocol_fee)?;
+            return Ok(());
+        }
+
+        let (Some(counter_vault), Some(maker_ata_b)) = (&self.counter_vault, &self.maker_ata_b)
+        else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+        let offer = counter_vault.amount;
+        let protocol_fee = self.config.protocol_fee(offer);
+
+        let escrow_key = self.escrow.key();
+        let taker_key = self.taker.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"counter",
+            escrow_key.as_ref(),
+            taker_key.as_ref(),
+            &[self.counter_offer.bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: counter_vault.to_account_info(),
+            mint: self.mint_b.to_account_info(),
+            to: maker_ata_b.to_account_info(),
+            authority: self.counter_offer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program_b.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, offer - protocol_fee, self.mint_b.decimals)?;
+
+        if protocol_fee > 0 {
+            let Some(treasury_ata_b) = &self.treasury_ata_b else {
+                return err!(EscrowError::MissingTokenAccount);
+            };
+            let transfer_accounts = TransferChecked {
+                from: counter_vault.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+                to: treasury_ata_b.to_account_info(),
+                authority: self.counter_offer.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program_b.to_account_info(),
+                transfer_accounts,
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, protocol_fee, self.mint_b.decimals)?;
+        }
+        harvest_withheld_fees(
+            &self.token_program_b.to_account_info(),
+            &self.mint_b.to_account_info(),
+            &counter_vault.to_account_info(),
+        )?;
+
+        let close_accounts = CloseAccount {
+            account: counter_vault.to_account_info(),
+            destination: self.taker.to_account_info(),
+            authority: self.counter_offer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program_b.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)
+    }
+
+    /// Sends the countered `amount` of `mint_a` to the taker. The rest of
+    /// the offer keeps its asking price, and a fully filled escrow closes.
+    pub fn release_deposit(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let amount = self.counter_offer.amount;
+        let cost = self
+            .escrow
+            .fill_cost(amount)
+            .ok_or(EscrowError::InvalidAmount)?;
+        self.escrow.deposit -= amount;
+        self.escrow.receive = self.escrow.receive.saturating_sub(cost);
+        let now = Clock::get()?.unix_timestamp;
+        self.offer_book.sync(self.escrow.key(), &self.escrow, now);
+        let filled = self.escrow.deposit == 0;
+
+        if self.escrow.sells_sol() {
+            self.escrow.sub_lamports(amount)?;
+            self.taker.add_lamports(amount)?;
+            if filled {
+                self.escrow.close(self.maker.to_account_info())?;
+            }
+            return Ok(());
+        }
+
+        let (Some(vault), Some(taker_ata_a)) = (&self.vault, &self.taker_ata_a) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+        let maker_key = self.maker.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"escrow",
+            maker_key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: taker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program_a.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        // The last fill sweeps the vault, including anything sent to it directly
+        let amount = if filled { vault.amount } else { amount };
+        transfer_checked(cpi_ctx, amount, self.mint_a.decimals)?;
+
+        if !filled {
+            return Ok(());
+        }
+        harvest_withheld_fees(
+            &self.token_program_a.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &vault.to_account_info(),
+        )?;
+
+        let close_accounts = CloseAccount {
+            account: vault.to_account_info(),
+            destination: self.maker.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program_a.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)?;
+
+        self.escrow.close(self.maker.to_account_info())?;
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked},
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
 };
-use crate::state::Escrow;
+use crate::error::EscrowError;
+use crate::state::{AllowedTakers, Escrow, OfferBook, OraclePricing, ReceiveMode};
+use crate::transfer::{transfer_checked, transfer_fee};
 
 #[derive(Accounts)]
 #[instruction(seed: u64)]
@@ -11,20 +14,21 @@ pub struct Make<'info> {
     #[account(mut)]
     pub maker: Signer<'info>,
     #[account(
-        mint::token_program = token_program
+        mint::token_program = token_program_a
     )]
     pub mint_a: InterfaceAccount<'info, Mint>,
     #[account(
-        mint::token_program = token_program
+        mint::token_program = token_program_b
     )]
     pub mint_b: InterfaceAccount<'info, Mint>,
+    /// Omitted when selling native SOL
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = maker,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_a
     )]
-    pub maker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         init,
         payer = maker,
@@ -33,43 +37,136 @@ pub struct Make<'info> {
         bump
     )]
     pub escrow: Account<'info, Escrow>,
+    #[account(
+        init_if_needed,
+        payer = maker,
+        seeds = [b"offer_book", mint_a.key().as_ref(), mint_b.key().as_ref()],
+        space = 8 + OfferBook::INIT_SPACE,
+        bump
+    )]
+    pub offer_book: Account<'info, OfferBook>,
+    /// Omitted when selling native SOL, which the escrow holds as lamports
     #[account(
         init,
         payer = maker,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_a
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Interface<'info, TokenInterface>,
+    /// Owner of `mint_a`; the native mint belongs to SPL Token
+    pub token_program_a: Interface<'info, TokenInterface>,
+    /// Owner of `mint_b`, which may differ from `mint_a`'s
+    pub token_program_b: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Make<'info> {
-    pub fn init_escrow(&mut self, seed: u64, receive: u64, bumps: &MakeBumps) -> Result<()> {
+    pub fn init_escrow(
+        &mut self,
+        seed: u64,
+        receive: u64,
+        deposit: u64,
+        min_fill: u64,
+        expires_at: Option<i64>,
+        bumps: &MakeBumps,
+    ) -> Result<()> {
+        require!(
+            deposit > 0 && min_fill <= deposit,
+            EscrowError::InvalidAmount
+        );
+        if let Some(expires_at) = expires_at {
+            require!(
+                expires_at > Clock::get()?.unix_timestamp,
+                EscrowError::InvalidExpiry
+            );
+        }
+
         self.escrow.set_inner(Escrow {
             seed,
             maker: self.maker.key(),
             mint_a: self.mint_a.key(),
             mint_b: self.mint_b.key(),
             receive,
+            receive_mode: ReceiveMode::Gross,
+            deposit,
+            min_fill,
+            expires_at,
+            allowed_takers: None,
+            oracle_pricing: None,
             bump: bumps.escrow,
         });
         Ok(())
     }
 
-    pub fn deposit(&mut self, deposit: u64) -> Result<()> {
+    pub fn restrict_takers(&mut self, allowed_takers: Option<AllowedTakers>) {
+        self.escrow.allowed_takers = allowed_takers;
+    }
+
+    pub fn set_receive_mode(&mut self, receive_mode: ReceiveMode) {
+        self.escrow.receive_mode = receive_mode;
+    }
+
+    /// Oracle-priced offers ignore `receive`; all others need one.
+    pub fn set_oracle_pricing(&mut self, oracle_pricing: Option<OraclePricing>) -> Result<()> {
+        match oracle_pricing {
+            Some(pricing) => require!(pricing.is_valid(), EscrowError::InvalidAmount),
+            None => require!(self.escrow.receive > 0, EscrowError::InvalidAmount),
+        }
+        self.escrow.oracle_pricing = oracle_pricing;
+        Ok(())
+    }
+
+    /// Lists the offer in its pair's book, creating the book for the first
+    /// offer on the pair.
+    pub fn list_offer(&mut self, bumps: &MakeBumps) -> Result<()> {
+        self.offer_book.mint_a = self.mint_a.key();
+        self.offer_book.mint_b = self.mint_b.key();
+        self.offer_book.min_deposit = OfferBook::min_deposit(self.mint_a.decimals);
+        self.offer_book.bump = bumps.offer_book;
+        let now = Clock::get()?.unix_timestamp;
+        self.offer_book.sync(self.escrow.key(), &self.escrow, now);
+        Ok(())
+    }
+
+    /// Moves the deposit into escrow, recording what actually arrived after
+    /// any `mint_a` transfer fee.
+    pub fn deposit(
+        &mut self,
+        deposit: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.escrow.sells_sol() {
+            // A native-mint vault would never be swept or closed
+            require!(
+                self.maker_ata_a.is_none() && self.vault.is_none(),
+                EscrowError::UnexpectedTokenAccount
+            );
+            let cpi_accounts = Transfer {
+                from: self.maker.to_account_info(),
+                to: self.escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            return transfer(cpi_ctx, deposit);
+        }
+
+        let (Some(maker_ata_a), Some(vault)) = (&self.maker_ata_a, &self.vault) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
         let transfer_accounts = TransferChecked {
-            from: self.maker_ata_a.to_account_info(),
+            from: maker_ata_a.to_account_info(),
             mint: self.mint_a.to_account_info(),
-            to: self.vault.to_account_info(),
+            to: vault.to_account_info(),
             authority: self.maker.to_account_info(),
         };
 
-        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
+        let cpi_ctx = CpiContext::new(self.token_program_a.to_account_info(), transfer_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
 
         transfer_checked(cpi_ctx, deposit, self.mint_a.decimals)?;
+
+        self.escrow.deposit = deposit - transfer_fee(&self.mint_a.to_account_info(), deposit)?;
         Ok(())
     }
 }
@@ -0,0 +1,358 @@
+use crate::error::EscrowError;
+use crate::state::{Milestone, MilestoneEscrow};
+use crate::transfer::{gross_amount, harvest_withheld_fees, transfer_checked};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct CreateMilestones<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"milestone", payer.key().as_ref(), seed.to_le_bytes().as_ref()],
+        space = 8 + MilestoneEscrow::INIT_SPACE,
+        bump
+    )]
+    pub milestone_escrow: Account<'info, MilestoneEscrow>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = milestone_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitMilestone<'info> {
+    pub payee: Signer<'info>,
+    #[account(
+        mut,
+        has_one = payee,
+        seeds = [
+            b"milestone",
+            milestone_escrow.payer.as_ref(),
+            milestone_escrow.seed.to_le_bytes().as_ref()
+        ],
+        bump = milestone_escrow.bump
+    )]
+    pub milestone_escrow: Account<'info, MilestoneEscrow>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveMilestone<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub payee: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = payee,
+        associated_token::token_program = token_program
+    )]
+    pub payee_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = payer,
+        has_one = payee,
+        has_one = mint,
+        seeds = [b"milestone", payer.key().as_ref(), milestone_escrow.seed.to_le_bytes().as_ref()],
+        bump = milestone_escrow.bump
+    )]
+    pub milestone_escrow: Box<Account<'info, MilestoneEscrow>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = milestone_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RefundMilestones<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub payee: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = payer,
+        associated_token::token_program = token_program
+    )]
+    pub payer_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Only needed while a submitted milestone is awaiting approval
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = payee,
+        associated_token::token_program = token_program
+    )]
+    pub payee_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = payer,
+        has_one = payee,
+        has_one = mint,
+        seeds = [b"milestone", payer.key().as_ref(), milestone_escrow.seed.to_le_bytes().as_ref()],
+        bump = milestone_escrow.bump
+    )]
+    pub milestone_escrow: Box<Account<'info, MilestoneEscrow>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = milestone_escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateMilestones<'info> {
+    pub fn init_milestones(
+        &mut self,
+        seed: u64,
+        payee: Pubkey,
+        milestones: Vec<Milestone>,
+        bumps: &CreateMilestonesBumps,
+    ) -> Result<()> {
+        require!(
+            MilestoneEscrow::valid_milestones(&milestones, Clock::get()?.unix_timestamp),
+            EscrowError::InvalidMilestones
+        );
+
+        self.milestone_escrow.set_inner(MilestoneEscrow {
+            seed,
+            payer: self.payer.key(),
+            payee,
+            mint: self.mint.key(),
+            milestones,
+            submitted: 0,
+            released: 0,
+            bump: bumps.milestone_escrow,
+        });
+        Ok(())
+    }
+
+    /// Deposits the sum of every milestone up front, grossed up so that the
+    /// whole sum reaches the vault after any transfer fee.
+    pub fn deposit(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let total = self
+            .milestone_escrow
+            .total()
+            .ok_or(EscrowError::InvalidMilestones)?;
+        let amount = gross_amount(&self.mint.to_account_info(), total)?;
+
+        let transfer_accounts = TransferChecked {
+            from: self.payer_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.vault.to_account_info(),
+            authority: self.payer.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)
+    }
+}
+
+impl<'info> SubmitMilestone<'info> {
+    /// Marks the next milestone complete; it must land before its deadline.
+    pub fn submit_milestone(&mut self) -> Result<()> {
+        let index = self.milestone_escrow.submitted as usize;
+        let milestone = self
+            .milestone_escrow
+            .milestones
+            .get(index)
+            .ok_or(EscrowError::InvalidStatus)?;
+        require!(
+            Clock::get()?.unix_timestamp <= milestone.deadline,
+            EscrowError::MilestoneOverdue
+        );
+
+        self.milestone_escrow.submitted += 1;
+        Ok(())
+    }
+}
+
+impl<'info> ApproveMilestone<'info> {
+    /// Pays out the next submitted milestone. The last one sweeps the vault
+    /// and closes the escrow.
+    pub fn approve_milestone(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let escrow = &self.milestone_escrow;
+        require!(
+            escrow.released < escrow.submitted,
+            EscrowError::InvalidStatus
+        );
+        let amount = escrow.milestones[escrow.released as usize].amount;
+        let last = escrow.released as usize + 1 == escrow.milestones.len();
+
+        let payer_key = self.payer.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"milestone",
+            payer_key.as_ref(),
+            &self.milestone_escrow.seed.to_le_bytes()[..],
+            &[self.milestone_escrow.bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.payee_ata.to_account_info(),
+            authority: self.milestone_escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        let amount = if last { self.vault.amount } else { amount };
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.milestone_escrow.released += 1;
+        if !last {
+            return Ok(());
+        }
+
+        // Close the vault account
+        harvest_withheld_fees(
+            &self.token_program.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault.to_account_info(),
+        )?;
+        let close_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.payer.to_account_info(),
+            authority: self.milestone_escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)?;
+
+        self.milestone_escrow.close(self.payer.to_account_info())?;
+        Ok(())
+    }
+}
+
+impl<'info> RefundMilestones<'info> {
+    /// Once the final deadline has passed, pays the payee for milestones
+    /// submitted in time but never approved and returns the rest to the
+    /// payer, so ignoring a submission cannot claw back delivered work.
+    pub fn refund_milestones(&mut self, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(
+            Clock::get()?.unix_timestamp > self.milestone_escrow.final_deadline(),
+            EscrowError::MilestonesPending
+        );
+
+        let payer_key = self.payer.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"milestone",
+            payer_key.as_ref(),
+            &self.milestone_escrow.seed.to_le_bytes()[..],
+            &[self.milestone_escrow.bump],
+        ]];
+
+        let owed = self.milestone_escrow.submitted_unreleased();
+        if owed > 0 {
+            let Some(payee_ata) = &self.payee_ata else {
+                return err!(EscrowError::MissingTokenAccount);
+            };
+            let transfer_accounts = TransferChecked {
+                from: self.vault.to_account_info(),
+                mint: self.mint.to_account_info(),
+                to: payee_ata.to_account_info(),
+                authority: self.milestone_escrow.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                transfer_accounts,
+                signer_seeds,
+            )
+            .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, owed, self.mint.decimals)?;
+            self.vault.reload()?;
+        }
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.payer_ata.to_account_info(),
+            authority: self.milestone_escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, self.vault.amount, self.mint.decimals)?;
+
+        // Close the vault account
+        harvest_withheld_fees(
+            &self.token_program.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault.to_account_info(),
+        )?;
+        let close_accounts = CloseAccount {
+            account: self.vault.to_account_info(),
+            destination: self.payer.to_account_info(),
+            authority: self.milestone_escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)
+    }
+}
@@ -1,5 +1,21 @@
 pub mod make;
 pub mod take ;
+pub mod refund;
+pub mod reclaim_expired;
+pub mod arbitrated;
+pub mod bundle;
+pub mod config;
+pub mod counter_offer;
+pub mod milestone;
+pub mod update_offer;
 
 pub use make::*;
-pub use take::*;
\ No newline at end of file
+pub use take::*;
+pub use refund::*;
+pub use reclaim_expired::*;
+pub use arbitrated::*;
+pub use bundle::*;
+pub use config::*;
+pub use counter_offer::*;
+pub use milestone::*;
+pub use update_offer::*;
\ No newline at end of file
@@ -0,0 +1,102 @@
+use super::refund::return_deposit;
+use crate::error::EscrowError;
+use crate::state::{Escrow, OfferBook};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+#[derive(Accounts)]
+pub struct ReclaimExpired<'info> {
+    /// Anyone may crank an expired offer
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    /// Omitted when the escrow sells native SOL. It must already exist, as
+    /// its rent would cost the cranker more than the reward.
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = mint_a,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        mut,
+        seeds = [b"offer_book", escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = offer_book.bump
+    )]
+    pub offer_book: Account<'info, OfferBook>,
+    /// Omitted when the escrow sells native SOL; that deposit is returned
+    /// along with the escrow's lamports
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ReclaimExpired<'info> {
+    pub fn delist_offer(&mut self) {
+        self.offer_book.remove(&self.escrow.key());
+    }
+
+    pub fn reclaim_and_close_vault(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(
+            self.escrow.is_expired(Clock::get()?.unix_timestamp),
+            EscrowError::OfferNotExpired
+        );
+
+        if self.escrow.sells_sol() {
+            return Ok(());
+        }
+
+        let (Some(vault), Some(maker_ata_a)) = (&self.vault, &self.maker_ata_a) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+
+        return_deposit(
+            &self.token_program,
+            &self.mint_a,
+            vault,
+            maker_ata_a,
+            &self.escrow,
+            self.maker.to_account_info(),
+            remaining_accounts,
+        )
+    }
+
+    /// Pays the cranker its cut of the escrow's rent; the `close = maker`
+    /// constraint then returns the rest, and any SOL deposit, to the maker.
+    pub fn pay_cranker(&mut self) -> Result<()> {
+        let rent = self.escrow.reclaimable_lamports(self.escrow.get_lamports());
+        let reward = rent * Escrow::RECLAIM_REWARD_BPS / 10_000;
+        self.escrow.sub_lamports(reward)?;
+        self.cranker.add_lamports(reward)?;
+        Ok(())
+    }
+}
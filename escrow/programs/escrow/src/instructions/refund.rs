@@ -0,0 +1,140 @@
+use crate::error::EscrowError;
+use crate::state::{Escrow, OfferBook};
+use crate::transfer::{harvest_withheld_fees, transfer_checked};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    /// Omitted when the escrow sells native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = maker,
+        has_one = maker,
+        has_one = mint_a,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        mut,
+        seeds = [b"offer_book", escrow.mint_a.as_ref(), escrow.mint_b.as_ref()],
+        bump = offer_book.bump
+    )]
+    pub offer_book: Account<'info, OfferBook>,
+    /// Omitted when the escrow sells native SOL; that deposit is returned
+    /// along with the escrow's lamports
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Refund<'info> {
+    pub fn delist_offer(&mut self) {
+        self.offer_book.remove(&self.escrow.key());
+    }
+
+    pub fn refund_and_close_vault(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        if self.escrow.sells_sol() {
+            return Ok(());
+        }
+
+        let (Some(vault), Some(maker_ata_a)) = (&self.vault, &self.maker_ata_a) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+
+        return_deposit(
+            &self.token_program,
+            &self.mint_a,
+            vault,
+            maker_ata_a,
+            &self.escrow,
+            self.maker.to_account_info(),
+            remaining_accounts,
+        )
+    }
+}
+
+/// Returns the whole deposit from `vault` to `maker_ata_a` and closes the
+/// vault, sending its rent to the maker.
+pub(crate) fn return_deposit<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint_a: &InterfaceAccount<'info, Mint>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    maker_ata_a: &InterfaceAccount<'info, TokenAccount>,
+    escrow: &Account<'info, Escrow>,
+    maker: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let maker_key = maker.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"escrow",
+        maker_key.as_ref(),
+        &escrow.seed.to_le_bytes()[..],
+        &[escrow.bump],
+    ]];
+
+    // Return the deposit from the vault to maker_ata_a
+    let transfer_accounts = TransferChecked {
+        from: vault.to_account_info(),
+        mint: mint_a.to_account_info(),
+        to: maker_ata_a.to_account_info(),
+        authority: escrow.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        transfer_accounts,
+        signer_seeds,
+    )
+    .with_remaining_accounts(remaining_accounts.to_vec());
+    transfer_checked(cpi_ctx, vault.amount, mint_a.decimals)?;
+
+    // Close the vault account
+    harvest_withheld_fees(
+        &token_program.to_account_info(),
+        &mint_a.to_account_info(),
+        &vault.to_account_info(),
+    )?;
+    let close_accounts = CloseAccount {
+        account: vault.to_account_info(),
+        destination: maker,
+        authority: escrow.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        close_accounts,
+        signer_seeds,
+    );
+    close_account(cpi_ctx)
+}
@@ -1,10 +1,13 @@
-use crate::state::Escrow;
+use crate::error::EscrowError;
+use crate::state::{Escrow, EscrowConfig, OfferBook, ReceiveMode};
+use crate::oracle::market_cost;
+use crate::transfer::{gross_amount, harvest_withheld_fees, transfer_checked};
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
 use anchor_spl::{
     associated_token::AssociatedToken,
     token_interface::{
-        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
-        TransferChecked,
+        close_account, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
     },
 };
 
@@ -13,106 +16,282 @@ use anchor_spl::{
 pub struct Take<'info> {
     #[account(mut)]
     pub taker: Signer<'info>,
+    #[account(mut)]
     pub maker: SystemAccount<'info>,
     #[account(
-        mint::token_program = token_program
+        mut,
+        mint::token_program = token_program_a
     )]
     pub mint_a: InterfaceAccount<'info, Mint>,
     #[account(
-        mint::token_program = token_program
+        mint::token_program = token_program_b
     )]
     pub mint_b: InterfaceAccount<'info, Mint>,
+    /// Omitted when the maker is paid in native SOL
     #[account(
         mut,
         associated_token::mint = mint_b,
         associated_token::authority = maker,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_b
     )]
-    pub maker_ata_b: InterfaceAccount<'info, TokenAccount>,
+    pub maker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Omitted when the maker is paid in native SOL
     #[account(
         mut,
         associated_token::mint = mint_b,
         associated_token::authority = taker,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_b
     )]
-    pub taker_ata_b: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Omitted when the escrow sells native SOL
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = taker,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_a
     )]
-    pub taker_ata_a: InterfaceAccount<'info, TokenAccount>,
+    pub taker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
+        has_one = maker,
+        has_one = mint_a,
+        has_one = mint_b,
         seeds = [b"escrow", maker.key().as_ref(), seed.to_le_bytes().as_ref()],
         bump = escrow.bump
     )]
     pub escrow: Account<'info, Escrow>,
+    #[account(
+        mut,
+        seeds = [b"offer_book", mint_a.key().as_ref(), mint_b.key().as_ref()],
+        bump = offer_book.bump
+    )]
+    pub offer_book: Box<Account<'info, OfferBook>>,
+    /// Omitted when the escrow sells native SOL
     #[account(
         mut,
         associated_token::mint = mint_a,
         associated_token::authority = escrow,
-        associated_token::token_program = token_program
+        associated_token::token_program = token_program_a
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump
+    )]
+    pub config: Account<'info, EscrowConfig>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = config.treasury_bump
+    )]
+    pub treasury: SystemAccount<'info>,
+    /// Omitted when the maker is paid in native SOL or no protocol fee is due
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = mint_b,
+        associated_token::authority = treasury,
+        associated_token::token_program = token_program_b
+    )]
+    pub treasury_ata_b: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Only for oracle-priced escrows; checked against the escrow's
+    /// oracle and parsed according to its format
+    pub oracle: Option<UncheckedAccount<'info>>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Interface<'info, TokenInterface>,
+    /// Owner of `mint_a`; the native mint belongs to SPL Token
+    pub token_program_a: Interface<'info, TokenInterface>,
+    /// Owner of `mint_b`, which may differ from `mint_a`'s
+    pub token_program_b: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Take<'info> {
-    pub fn deposit(&mut self) -> Result<()> {
-        let transfer_accounts = TransferChecked {
-            from: self.taker_ata_b.to_account_info(),
-            mint: self.mint_b.to_account_info(),
-            to: self.maker_ata_b.to_account_info(),
-            authority: self.taker.to_account_info(),
+    pub fn check_taker(&self, proof: &[[u8; 32]]) -> Result<()> {
+        require!(
+            self.escrow.is_allowed_taker(&self.taker.key(), proof),
+            EscrowError::TakerNotAllowed
+        );
+        Ok(())
+    }
+
+    /// `mint_b` owed for `amount` of the deposit, pro rata or at the
+    /// oracle's market price.
+    fn cost(&self, amount: u64) -> Result<u64> {
+        let Some(pricing) = self.escrow.oracle_pricing else {
+            let cost = self
+                .escrow
+                .fill_cost(amount)
+                .ok_or(EscrowError::InvalidAmount)?;
+            return Ok(cost);
+        };
+        let Some(oracle) = &self.oracle else {
+            return err!(EscrowError::InvalidOracle);
         };
+        market_cost(
+            &pricing,
+            oracle,
+            amount,
+            self.mint_a.decimals,
+            self.mint_b.decimals,
+        )
+    }
+
+    /// Pays the maker the `mint_b` owed for `amount` of the deposit, less
+    /// the protocol fee, which goes to the treasury. `max_cost` caps that
+    /// payment before transfer fees.
+    pub fn deposit(
+        &mut self,
+        amount: u64,
+        max_cost: Option<u64>,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.escrow
+            .ensure_fillable(amount, Clock::get()?.unix_timestamp)?;
+        let cost = self.cost(amount)?;
+        if let Some(max_cost) = max_cost {
+            require!(cost <= max_cost, EscrowError::SlippageExceeded);
+        }
+        let protocol_fee = self.config.protocol_fee(cost);
+        let maker_share = cost - protocol_fee;
+
+        if self.escrow.buys_sol() {
+            let cpi_accounts = Transfer {
+                from: self.taker.to_account_info(),
+                to: self.maker.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, maker_share)?;
+
+            if protocol_fee > 0 {
+                let cpi_accounts = Transfer {
+                    from: self.taker.to_account_info(),
+                    to: self.treasury.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+                transfer(cpi_ctx, protocol_fee)?;
+            }
+        } else {
+            let (Some(taker_ata_b), Some(maker_ata_b)) = (&self.taker_ata_b, &self.maker_ata_b)
+            else {
+                return err!(EscrowError::MissingTokenAccount);
+            };
+            let transfer_accounts = TransferChecked {
+                from: taker_ata_b.to_account_info(),
+                mint: self.mint_b.to_account_info(),
+                to: maker_ata_b.to_account_info(),
+                authority: self.taker.to_account_info(),
+            };
+
+            let gross = match self.escrow.receive_mode {
+                ReceiveMode::Gross => maker_share,
+                ReceiveMode::Net => gross_amount(&self.mint_b.to_account_info(), maker_share)?,
+            };
+
+            let cpi_ctx =
+                CpiContext::new(self.token_program_b.to_account_info(), transfer_accounts)
+                    .with_remaining_accounts(remaining_accounts.to_vec());
+            transfer_checked(cpi_ctx, gross, self.mint_b.decimals)?;
 
-        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
-        transfer_checked(cpi_ctx, self.escrow.receive, self.mint_b.decimals)?;
+            if protocol_fee > 0 {
+                let Some(treasury_ata_b) = &self.treasury_ata_b else {
+                    return err!(EscrowError::MissingTokenAccount);
+                };
+                let transfer_accounts = TransferChecked {
+                    from: taker_ata_b.to_account_info(),
+                    mint: self.mint_b.to_account_info(),
+                    to: treasury_ata_b.to_account_info(),
+                    authority: self.taker.to_account_info(),
+                };
 
+                let cpi_ctx =
+                    CpiContext::new(self.token_program_b.to_account_info(), transfer_accounts)
+                        .with_remaining_accounts(remaining_accounts.to_vec());
+                transfer_checked(cpi_ctx, protocol_fee, self.mint_b.decimals)?;
+            }
+        }
+
+        self.escrow.deposit -= amount;
+        self.escrow.receive = self.escrow.receive.saturating_sub(cost);
+        Ok(())
+    }
+
+    /// Relists the rest of a partly filled offer, or delists a filled one.
+    pub fn sync_offer_book(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        self.offer_book.sync(self.escrow.key(), &self.escrow, now);
         Ok(())
     }
 
-    pub fn withdraw_and_close(&mut self) -> Result<()> {
+    /// Sends `amount` of `mint_a` to the taker, closing the vault and the
+    /// escrow once the offer is fully filled.
+    pub fn withdraw_and_close(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let filled = self.escrow.deposit == 0;
+        if self.escrow.sells_sol() {
+            self.escrow.sub_lamports(amount)?;
+            self.taker.add_lamports(amount)?;
+            if filled {
+                self.escrow.close(self.maker.to_account_info())?;
+            }
+            return Ok(());
+        }
+
+        let (Some(vault), Some(taker_ata_a)) = (&self.vault, &self.taker_ata_a) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+        let maker_key = self.maker.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             b"escrow",
-            self.maker.key().as_ref(),
+            maker_key.as_ref(),
             &self.escrow.seed.to_le_bytes()[..],
             &[self.escrow.bump],
         ]];
 
         // Transfer tokens from vault to taker_ata_a
         let transfer_accounts = TransferChecked {
-            from: self.vault.to_account_info(),
+            from: vault.to_account_info(),
             mint: self.mint_a.to_account_info(),
-            to: self.taker_ata_a.to_account_info(),
+            to: taker_ata_a.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
 
         let cpi_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
+            self.token_program_a.to_account_info(),
             transfer_accounts,
             signer_seeds,
-        );
-        transfer_checked(cpi_ctx, self.vault.amount, self.mint_a.decimals)?;
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        // The last fill sweeps the vault, including anything sent to it directly
+        let amount = if filled { vault.amount } else { amount };
+        transfer_checked(cpi_ctx, amount, self.mint_a.decimals)?;
+
+        if !filled {
+            return Ok(());
+        }
 
         // Close the vault account
+        harvest_withheld_fees(
+            &self.token_program_a.to_account_info(),
+            &self.mint_a.to_account_info(),
+            &vault.to_account_info(),
+        )?;
         let close_accounts = CloseAccount {
-            account: self.vault.to_account_info(),
+            account: vault.to_account_info(),
             destination: self.maker.to_account_info(),
             authority: self.escrow.to_account_info(),
         };
 
         let cpi_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
+            self.token_program_a.to_account_info(),
             close_accounts,
             signer_seeds,
         );
         close_account(cpi_ctx)?;
 
+        self.escrow.close(self.maker.to_account_info())?;
         Ok(())
     }
 }
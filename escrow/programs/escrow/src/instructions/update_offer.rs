@@ -0,0 +1,156 @@
+use crate::error::EscrowError;
+use crate::state::{Escrow, OfferBook};
+use crate::transfer::{transfer_checked, transfer_fee};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+#[derive(Accounts)]
+pub struct UpdateOffer<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+    #[account(
+        mint::token_program = token_program
+    )]
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    /// Omitted when the escrow sells native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program
+    )]
+    pub maker_ata_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        has_one = maker,
+        has_one = mint_a,
+        seeds = [b"escrow", maker.key().as_ref(), escrow.seed.to_le_bytes().as_ref()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, Escrow>,
+    #[account(
+        mut,
+        seeds = [b"offer_book", mint_a.key().as_ref(), escrow.mint_b.as_ref()],
+        bump = offer_book.bump
+    )]
+    pub offer_book: Account<'info, OfferBook>,
+    /// Omitted when the escrow sells native SOL
+    #[account(
+        mut,
+        associated_token::mint = mint_a,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpdateOffer<'info> {
+    /// Renegotiates an open offer in place. `receive` replaces what is still
+    /// owed for the whole remaining deposit, so a top-up or withdrawal
+    /// without a new `receive` changes the implied price.
+    pub fn update_offer(
+        &mut self,
+        receive: Option<u64>,
+        top_up: u64,
+        withdraw: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(
+            !self.escrow.is_expired(Clock::get()?.unix_timestamp),
+            EscrowError::OfferExpired
+        );
+
+        if top_up > 0 {
+            self.top_up(top_up, remaining_accounts)?;
+        }
+        if withdraw > 0 {
+            self.withdraw(withdraw, remaining_accounts)?;
+        }
+        if let Some(receive) = receive {
+            self.escrow.receive = receive;
+        }
+
+        require!(
+            self.escrow.deposit > 0
+                && (self.escrow.receive > 0 || self.escrow.oracle_pricing.is_some()),
+            EscrowError::InvalidAmount
+        );
+        let now = Clock::get()?.unix_timestamp;
+        self.offer_book.sync(self.escrow.key(), &self.escrow, now);
+        Ok(())
+    }
+
+    fn top_up(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        if self.escrow.sells_sol() {
+            let cpi_accounts = Transfer {
+                from: self.maker.to_account_info(),
+                to: self.escrow.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+            transfer(cpi_ctx, amount)?;
+            self.escrow.deposit += amount;
+            return Ok(());
+        }
+
+        let (Some(maker_ata_a), Some(vault)) = (&self.maker_ata_a, &self.vault) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+        let transfer_accounts = TransferChecked {
+            from: maker_ata_a.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: vault.to_account_info(),
+            authority: self.maker.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts)
+            .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, self.mint_a.decimals)?;
+
+        self.escrow.deposit += amount - transfer_fee(&self.mint_a.to_account_info(), amount)?;
+        Ok(())
+    }
+
+    fn withdraw(&mut self, amount: u64, remaining_accounts: &[AccountInfo<'info>]) -> Result<()> {
+        require!(amount < self.escrow.deposit, EscrowError::InvalidAmount);
+        self.escrow.deposit -= amount;
+
+        if self.escrow.sells_sol() {
+            self.escrow.sub_lamports(amount)?;
+            self.maker.add_lamports(amount)?;
+            return Ok(());
+        }
+
+        let (Some(maker_ata_a), Some(vault)) = (&self.maker_ata_a, &self.vault) else {
+            return err!(EscrowError::MissingTokenAccount);
+        };
+        let maker_key = self.maker.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"escrow",
+            maker_key.as_ref(),
+            &self.escrow.seed.to_le_bytes()[..],
+            &[self.escrow.bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: vault.to_account_info(),
+            mint: self.mint_a.to_account_info(),
+            to: maker_ata_a.to_account_info(),
+            authority: self.escrow.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        )
+        .with_remaining_accounts(remaining_accounts.to_vec());
+        transfer_checked(cpi_ctx, amount, self.mint_a.decimals)
+    }
+}
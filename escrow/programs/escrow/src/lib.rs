@@ -5,22 +5,234 @@ mod instructions ;
 use instructions::*;
 
 mod state ;
+use state::{AllowedTakers, BundleLeg, Milestone, OraclePricing, ReceiveMode};
+mod error;
+mod oracle;
+mod transfer;
 
 
 #[program]
+// `seed` is only consumed by the account seed constraints
+#[allow(unused_variables)]
 pub mod escrow {
     use super::*;
 
-    pub fn make(ctx: Context<Make>, seed :u64,receive : u64,deposit :u64) -> Result<()> {
-        ctx.accounts.init_escrow(seed,recieve , &ctx.bumps)?;
-        ctx.accounts.deposit(deposit)?;
-        Ok(())  
+    /// Remaining accounts: transfer-hook extra accounts for `mint_a`, if any
+    #[allow(clippy::too_many_arguments)]
+    pub fn make<'info>(
+        ctx: Context<'_, '_, '_, 'info, Make<'info>>,
+        seed: u64,
+        receive: u64,
+        deposit: u64,
+        min_fill: u64,
+        expires_at: Option<i64>,
+        allowed_takers: Option<AllowedTakers>,
+        receive_mode: ReceiveMode,
+        oracle_pricing: Option<OraclePricing>,
+    ) -> Result<()> {
+        ctx.accounts.init_escrow(seed, receive, deposit, min_fill, expires_at, &ctx.bumps)?;
+        ctx.accounts.restrict_takers(allowed_takers);
+        ctx.accounts.set_receive_mode(receive_mode);
+        ctx.accounts.set_oracle_pricing(oracle_pricing)?;
+        ctx.accounts.deposit(deposit, ctx.remaining_accounts)?;
+        ctx.accounts.list_offer(&ctx.bumps)?;
+        Ok(())
     }
-    pub fn take(ctx: Context<Take>, seed :u64,receive : u64,deposit :u64) -> Result<()> {
-       
-        ctx.accounts.deposit()?;
-        ctx.accounts.withdraw_and_close()?;
-        Ok(())  
+
+    /// Remaining accounts: transfer-hook extra accounts for either mint, if any
+    pub fn take<'info>(
+        ctx: Context<'_, '_, '_, 'info, Take<'info>>,
+        seed: u64,
+        amount: u64,
+        max_cost: Option<u64>,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        ctx.accounts.check_taker(&proof)?;
+        ctx.accounts.deposit(amount, max_cost, ctx.remaining_accounts)?;
+        ctx.accounts.sync_offer_book()?;
+        ctx.accounts.withdraw_and_close(amount, ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint_a`, if any
+    pub fn refund<'info>(ctx: Context<'_, '_, '_, 'info, Refund<'info>>) -> Result<()> {
+        ctx.accounts.delist_offer();
+        ctx.accounts.refund_and_close_vault(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint_a`, if any
+    pub fn reclaim_expired<'info>(
+        ctx: Context<'_, '_, '_, 'info, ReclaimExpired<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.reclaim_and_close_vault(ctx.remaining_accounts)?;
+        ctx.accounts.delist_offer();
+        ctx.accounts.pay_cranker()
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint_a`, if any
+    pub fn update_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateOffer<'info>>,
+        receive: Option<u64>,
+        top_up: u64,
+        withdraw: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .update_offer(receive, top_up, withdraw, ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint_b`, if any
+    pub fn make_counter_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeCounterOffer<'info>>,
+        amount: u64,
+        offer: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        ctx.accounts
+            .make_counter_offer(amount, offer, &proof, ctx.remaining_accounts, &ctx.bumps)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint_b`, if any
+    pub fn cancel_counter_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelCounterOffer<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.cancel_counter_offer(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for either mint, if any
+    pub fn accept_counter_offer<'info>(
+        ctx: Context<'_, '_, '_, 'info, AcceptCounterOffer<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.pay_maker(ctx.remaining_accounts)?;
+        ctx.accounts.release_deposit(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn open_arbitrated<'info>(
+        ctx: Context<'_, '_, '_, 'info, OpenArbitrated<'info>>,
+        seed: u64,
+        seller: Pubkey,
+        arbiter: Pubkey,
+        amount: u64,
+        dispute_window: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .open_arbitrated(seed, seller, arbiter, dispute_window, &ctx.bumps)?;
+        ctx.accounts.fund(amount, ctx.remaining_accounts)
+    }
+
+    pub fn mark_delivered(ctx: Context<UpdateArbitrated>) -> Result<()> {
+        ctx.accounts.mark_delivered()
+    }
+
+    pub fn open_dispute(ctx: Context<UpdateArbitrated>) -> Result<()> {
+        ctx.accounts.open_dispute()
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn confirm_delivery<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleArbitrated<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.confirm_delivery(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn release_after_window<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleArbitrated<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.release_after_window(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn resolve_dispute<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleArbitrated<'info>>,
+        seller_amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .resolve_dispute(seller_amount, ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn refund_unresolved<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleArbitrated<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.refund_unresolved(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn create_milestones<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateMilestones<'info>>,
+        seed: u64,
+        payee: Pubkey,
+        milestones: Vec<Milestone>,
+    ) -> Result<()> {
+        ctx.accounts
+            .init_milestones(seed, payee, milestones, &ctx.bumps)?;
+        ctx.accounts.deposit(ctx.remaining_accounts)
+    }
+
+    pub fn submit_milestone(ctx: Context<SubmitMilestone>) -> Result<()> {
+        ctx.accounts.submit_milestone()
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn approve_milestone<'info>(
+        ctx: Context<'_, '_, '_, 'info, ApproveMilestone<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.approve_milestone(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn refund_milestones<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundMilestones<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.refund_milestones(ctx.remaining_accounts)
+    }
+
+    /// Part of deploying the program: `take` and `accept_counter_offer`
+    /// read the fee from this config, so they fail until it exists. Only the
+    /// upgrade authority can call it; pass a `fee_bps` of zero for no fee.
+    pub fn init_config(ctx: Context<InitConfig>, fee_bps: u16) -> Result<()> {
+        ctx.accounts.init_config(fee_bps, &ctx.bumps)
+    }
+
+    pub fn set_fee(ctx: Context<UpdateConfig>, fee_bps: u16) -> Result<()> {
+        ctx.accounts.set_fee(fee_bps)
+    }
+
+    /// Remaining accounts: transfer-hook extra accounts for `mint`, if any
+    pub fn withdraw_treasury<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawTreasury<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .withdraw_treasury(amount, ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: `[mint, maker_ata, vault]` per offered leg, then
+    /// transfer-hook extra accounts for any leg
+    pub fn make_bundle<'info>(
+        ctx: Context<'_, '_, '_, 'info, MakeBundle<'info>>,
+        seed: u64,
+        offered: Vec<BundleLeg>,
+        requested: Vec<BundleLeg>,
+    ) -> Result<()> {
+        ctx.accounts
+            .make_bundle(seed, offered, requested, ctx.remaining_accounts, &ctx.bumps)
+    }
+
+    /// Remaining accounts: `[mint, taker_ata, maker_ata]` per requested leg,
+    /// then `[mint (writable), vault, taker_ata]` per offered leg, then
+    /// transfer-hook extra accounts for any leg
+    pub fn take_bundle<'info>(ctx: Context<'_, '_, '_, 'info, TakeBundle<'info>>) -> Result<()> {
+        ctx.accounts.take_bundle(ctx.remaining_accounts)
+    }
+
+    /// Remaining accounts: `[mint (writable), vault, maker_ata]` per offered
+    /// leg, then transfer-hook extra accounts for any leg
+    pub fn refund_bundle<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundBundle<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.refund_bundle(ctx.remaining_accounts)
     }
 }
 
@@ -0,0 +1,327 @@
+use anchor_lang::prelude::*;
+
+use crate::error::EscrowError;
+use crate::state::{OracleFormat, OraclePricing};
+
+/// Owner of `OracleFormat::Mock` feeds, the `mock-oracle` program in this
+/// workspace
+#[cfg(feature = "mock-oracle")]
+const MOCK_ORACLE_ID: Pubkey = pubkey!("AGfGiRXpUQTkENhGEgHwrSYYYFZb7CP1NrNQo81UNSUV");
+#[cfg(feature = "mock-oracle")]
+const MOCK_FEED_DISCRIMINATOR: [u8; 8] = [189, 103, 252, 23, 152, 35, 243, 156];
+
+/// Pyth Solana receiver, which owns `PriceUpdateV2` accounts
+const PYTH_RECEIVER_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// `price * 10^expo` whole `mint_b` per whole `mint_a`, give or take
+/// `conf * 10^expo`
+pub struct OraclePrice {
+    pub price: i64,
+    pub expo: i32,
+    pub conf: u64,
+    pub publish_time: i64,
+}
+
+#[cfg(feature = "mock-oracle")]
+#[derive(AnchorDeserialize)]
+struct MockFeed {
+    _authority: Pubkey,
+    price: i64,
+    expo: i32,
+    conf: u64,
+    publish_time: i64,
+}
+
+#[derive(AnchorDeserialize)]
+struct PythPriceUpdate {
+    _write_authority: Pubkey,
+    verification_level: PythVerificationLevel,
+    feed_id: [u8; 32],
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+}
+
+#[derive(AnchorDeserialize, PartialEq, Eq)]
+enum PythVerificationLevel {
+    Partial { _num_signatures: u8 },
+    Full,
+}
+
+/// Reads `oracle` according to the pricing's format, checking it is owned by
+/// the format's program and, for Pyth, carries the expected feed.
+pub fn read_price(pricing: &OraclePricing, oracle: &AccountInfo) -> Result<OraclePrice> {
+    let (owner, discriminator) = match pricing.format {
+        OracleFormat::PythPriceUpdateV2 => (PYTH_RECEIVER_ID, PYTH_PRICE_UPDATE_DISCRIMINATOR),
+        #[cfg(feature = "mock-oracle")]
+        OracleFormat::Mock => (MOCK_ORACLE_ID, MOCK_FEED_DISCRIMINATOR),
+    };
+    require_keys_eq!(*oracle.owner, owner, EscrowError::InvalidOracle);
+
+    let data = oracle.try_borrow_data()?;
+    let Some(mut body) = data.strip_prefix(&discriminator[..]) else {
+        return err!(EscrowError::InvalidOracle);
+    };
+
+    match pricing.format {
+        OracleFormat::PythPriceUpdateV2 => {
+            let update = PythPriceUpdate::deserialize(&mut body)?;
+            require!(
+                update.verification_level == PythVerificationLevel::Full
+                    && update.feed_id == pricing.feed_id,
+                EscrowError::InvalidOracle
+            );
+            Ok(OraclePrice {
+                price: update.price,
+                expo: update.exponent,
+                conf: update.conf,
+                publish_time: update.publish_time,
+            })
+        }
+        #[cfg(feature = "mock-oracle")]
+        OracleFormat::Mock => {
+            let feed = MockFeed::deserialize(&mut body)?;
+            Ok(OraclePrice {
+                price: feed.price,
+                expo: feed.expo,
+                conf: feed.conf,
+                publish_time: feed.publish_time,
+            })
+        }
+    }
+}
+
+/// `mint_b` owed for `amount` of `mint_a` at the oracle's current price.
+pub fn market_cost(
+    pricing: &OraclePricing,
+    oracle: &AccountInfo,
+    amount: u64,
+    decimals_a: u8,
+    decimals_b: u8,
+) -> Result<u64> {
+    require_keys_eq!(oracle.key(), pricing.oracle, EscrowError::InvalidOracle);
+    let price = read_price(pricing, oracle)?;
+    let now = Clock::get()?.unix_timestamp;
+    oracle_cost(pricing, &price, now, amount, decimals_a, decimals_b)
+}
+
+/// `mint_b` owed for `amount` of `mint_a` at `price` plus the maker's
+/// spread, rounded up in the maker's favour. Fails if the price is stale at
+/// `now`, less certain than the maker allows or outside the maker's bounds.
+pub fn oracle_cost(
+    pricing: &OraclePricing,
+    price: &OraclePrice,
+    now: i64,
+    amount: u64,
+    decimals_a: u8,
+    decimals_b: u8,
+) -> Result<u64> {
+    require!(
+        now.saturating_sub(price.publish_time) <= pricing.max_staleness,
+        EscrowError::OracleStale
+    );
+    require!(price.price > 0, EscrowError::InvalidOracle);
+    require!(
+        price.conf as u128 * 10_000 <= price.price as u128 * pricing.max_conf_bps as u128,
+        EscrowError::OracleConfidenceTooWide
+    );
+
+    // Price per whole `mint_a` in `mint_b` base units, as a fraction
+    let scale = price.expo + decimals_b as i32;
+    let pow = 10u128
+        .checked_pow(scale.unsigned_abs())
+        .ok_or(EscrowError::InvalidOracle)?;
+    let mut numerator = price.price as u128 * (10_000 + pricing.spread_bps as i32) as u128;
+    let mut denominator = 10_000u128;
+    if scale >= 0 {
+        numerator = numerator
+            .checked_mul(pow)
+            .ok_or(EscrowError::InvalidOracle)?;
+    } else {
+        denominator = denominator
+            .checked_mul(pow)
+            .ok_or(EscrowError::InvalidOracle)?;
+    }
+
+    let at_least_min = (pricing.min_price as u128)
+        .checked_mul(denominator)
+        .is_some_and(|min| numerator >= min);
+    // A maximum too large to scale cannot be exceeded
+    let at_most_max = match (pricing.max_price as u128).checked_mul(denominator) {
+        Some(max) => numerator <= max,
+        None => true,
+    };
+    require!(
+        at_least_min && at_most_max,
+        EscrowError::OraclePriceOutOfBounds
+    );
+
+    let unit_a = 10u128
+        .checked_pow(decimals_a as u32)
+        .ok_or(EscrowError::InvalidOracle)?;
+    let cost = (amount as u128)
+        .checked_mul(numerator)
+        .zip(denominator.checked_mul(unit_a))
+        .and_then(|(cost, denominator)| u64::try_from(cost.div_ceil(denominator)).ok())
+        .ok_or(EscrowError::InvalidAmount)?;
+    Ok(cost)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 150 `mint_b` per `mint_a` give or take 0.15, published at time 1_000
+    const PRICE: OraclePrice = OraclePrice {
+        price: 150_000_000,
+        expo: -6,
+        conf: 150_000,
+        publish_time: 1_000,
+    };
+    /// Two whole `mint_a` at 9 decimals
+    const TWO_A: u64 = 2_000_000_000;
+
+    fn pricing(spread_bps: i16, min_price: u64, max_price: u64) -> OraclePricing {
+        OraclePricing {
+            oracle: Pubkey::new_unique(),
+            format: OracleFormat::PythPriceUpdateV2,
+            feed_id: [1; 32],
+            spread_bps,
+            max_staleness: 60,
+            max_conf_bps: 10,
+            min_price,
+            max_price,
+        }
+    }
+
+    fn cost(pricing: &OraclePricing, price: &OraclePrice, now: i64, amount: u64) -> Result<u64> {
+        oracle_cost(pricing, price, now, amount, 9, 6)
+    }
+
+    #[test]
+    fn prices_at_the_oracle_rate_plus_spread() {
+        assert_eq!(
+            cost(&pricing(0, 0, u64::MAX), &PRICE, 1_000, TWO_A),
+            Ok(300_000_000)
+        );
+        assert_eq!(
+            cost(&pricing(100, 0, u64::MAX), &PRICE, 1_000, TWO_A),
+            Ok(303_000_000)
+        );
+        assert_eq!(
+            cost(&pricing(-100, 0, u64::MAX), &PRICE, 1_000, TWO_A),
+            Ok(297_000_000)
+        );
+    }
+
+    #[test]
+    fn scales_between_exponent_and_decimals() {
+        // 1.5 `mint_b` per `mint_a`, with `mint_b` at 2 decimals
+        let price = OraclePrice {
+            price: 15,
+            expo: -1,
+            conf: 0,
+            publish_time: 1_000,
+        };
+        assert_eq!(
+            oracle_cost(&pricing(0, 0, u64::MAX), &price, 1_000, 4, 0, 2),
+            Ok(600)
+        );
+        // And with an exponent finer than `mint_b`'s decimals
+        let price = OraclePrice {
+            price: 150_000_000_000,
+            expo: -9,
+            conf: 0,
+            publish_time: 1_000,
+        };
+        assert_eq!(
+            cost(&pricing(0, 0, u64::MAX), &price, 1_000, TWO_A),
+            Ok(300_000_000)
+        );
+    }
+
+    #[test]
+    fn rounds_up_for_the_maker() {
+        // One base unit of `mint_a` is worth 0.15 base units of `mint_b`
+        assert_eq!(cost(&pricing(0, 0, u64::MAX), &PRICE, 1_000, 1), Ok(1));
+    }
+
+    #[test]
+    fn rejects_stale_prices() {
+        let pricing = pricing(0, 0, u64::MAX);
+        assert!(cost(&pricing, &PRICE, 1_060, TWO_A).is_ok());
+        assert_eq!(
+            cost(&pricing, &PRICE, 1_061, TWO_A),
+            Err(EscrowError::OracleStale.into())
+        );
+    }
+
+    #[test]
+    fn rejects_prices_with_a_wide_confidence_interval() {
+        let mut pricing = pricing(0, 0, u64::MAX);
+        // 0.15 on 150 is exactly 10 bps
+        assert!(cost(&pricing, &PRICE, 1_000, TWO_A).is_ok());
+        pricing.max_conf_bps = 9;
+        assert_eq!(
+            cost(&pricing, &PRICE, 1_000, TWO_A),
+            Err(EscrowError::OracleConfidenceTooWide.into())
+        );
+    }
+
+    #[test]
+    fn rejects_prices_outside_the_bounds() {
+        assert!(cost(&pricing(0, 150_000_000, 150_000_000), &PRICE, 1_000, TWO_A).is_ok());
+        assert_eq!(
+            cost(&pricing(0, 151_000_000, u64::MAX), &PRICE, 1_000, TWO_A),
+            Err(EscrowError::OraclePriceOutOfBounds.into())
+        );
+        assert_eq!(
+            cost(&pricing(0, 0, 149_000_000), &PRICE, 1_000, TWO_A),
+            Err(EscrowError::OraclePriceOutOfBounds.into())
+        );
+        // Bounds apply after the spread
+        assert_eq!(
+            cost(&pricing(100, 0, 150_000_000), &PRICE, 1_000, TWO_A),
+            Err(EscrowError::OraclePriceOutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn rejects_nonsensical_prices_and_decimals() {
+        let pricing = pricing(0, 0, u64::MAX);
+        let negative = OraclePrice { price: -1, ..PRICE };
+        assert_eq!(
+            cost(&pricing, &negative, 1_000, TWO_A),
+            Err(EscrowError::InvalidOracle.into())
+        );
+        let huge_expo = OraclePrice { expo: 50, ..PRICE };
+        assert_eq!(
+            cost(&pricing, &huge_expo, 1_000, TWO_A),
+            Err(EscrowError::InvalidOracle.into())
+        );
+        assert_eq!(
+            oracle_cost(&pricing, &PRICE, 1_000, TWO_A, u8::MAX, 6),
+            Err(EscrowError::InvalidOracle.into())
+        );
+    }
+
+    #[test]
+    fn pyth_pricing_needs_a_feed_id() {
+        let mut pricing = pricing(0, 0, u64::MAX);
+        assert!(pricing.is_valid());
+        pricing.feed_id = [0; 32];
+        assert!(!pricing.is_valid());
+    }
+
+    #[cfg(not(feature = "mock-oracle"))]
+    #[test]
+    fn release_builds_cannot_decode_mock_pricing() {
+        let mut data = Vec::new();
+        pricing(0, 0, u64::MAX).serialize(&mut data).unwrap();
+        // `format` follows the oracle key, and `Mock` would be its second variant
+        data[32] = 1;
+        assert!(OraclePricing::try_from_slice(&data).is_err());
+    }
+}
@@ -1,12 +1,813 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_spl::token::spl_token::native_mint;
+
+use crate::error::EscrowError;
 
 #[account]
 #[derive(InitSpace)]
 pub struct Escrow {
     pub seed : u64,
-    pub maker : pubkey,
-    pub mint_a : pubkey,
-    pub mint_b : pubkey ,
+    pub maker : Pubkey,
+    pub mint_a : Pubkey,
+    pub mint_b : Pubkey ,
+    /// `mint_b` still owed for the unfilled part of the offer; unused when
+    /// `oracle_pricing` is set
     pub receive : u64 ,
+    /// Whether `receive` is before or after `mint_b` transfer fees
+    pub receive_mode: ReceiveMode,
+    /// `mint_a` still held in the vault for takers
+    pub deposit: u64,
+    /// Smallest `mint_a` amount a single take may fill, bar the last one
+    pub min_fill: u64,
+    pub expires_at: Option<i64>,
+    /// Restricts who may take the offer; `None` leaves it open to anyone
+    pub allowed_takers: Option<AllowedTakers>,
+    /// Prices fills off an oracle instead of `receive`
+    pub oracle_pricing: Option<OraclePricing>,
     pub bump : u8,
-}
\ No newline at end of file
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ReceiveMode {
+    /// The taker sends `receive` and any transfer fee comes out of it
+    Gross,
+    /// The maker ends up with `receive`; the taker also covers the fee
+    Net,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum AllowedTakers {
+    Taker(Pubkey),
+    /// Root of a tree whose leaves are `hash(taker)` and whose nodes hash
+    /// their children in sorted order
+    MerkleRoot([u8; 32]),
+}
+
+/// Sells `mint_a` at the oracle's `mint_b` price plus a spread, within the
+/// maker's bounds.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct OraclePricing {
+    /// Feed pricing `mint_a` in `mint_b`
+    pub oracle: Pubkey,
+    pub format: OracleFormat,
+    /// Pyth price feed the oracle account must carry; unused for mock feeds
+    pub feed_id: [u8; 32],
+    /// Premium over the oracle price; negative sells below market
+    pub spread_bps: i16,
+    /// Oldest oracle update a take accepts, in seconds
+    pub max_staleness: i64,
+    /// Widest confidence interval a take accepts, in bps of the price
+    pub max_conf_bps: u16,
+    /// Prices the maker will sell at, after the spread, in `mint_b` base
+    /// units per whole `mint_a` token
+    pub min_price: u64,
+    pub max_price: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum OracleFormat {
+    /// Fully verified Pyth `PriceUpdateV2` accounts
+    PythPriceUpdateV2,
+    /// `PriceFeed` accounts of the workspace's `mock-oracle` program, only
+    /// compiled into builds with the `mock-oracle` feature
+    #[cfg(feature = "mock-oracle")]
+    Mock,
+}
+
+impl OraclePricing {
+    pub fn is_valid(&self) -> bool {
+        self.spread_bps > -10_000
+            && self.max_staleness > 0
+            && self.min_price <= self.max_price
+            && match self.format {
+                OracleFormat::PythPriceUpdateV2 => self.feed_id != [0; 32],
+                #[cfg(feature = "mock-oracle")]
+                OracleFormat::Mock => true,
+            }
+    }
+}
+
+impl Escrow {
+    /// Share of the escrow's rent paid to whoever reclaims an expired offer
+    pub const RECLAIM_REWARD_BPS: u64 = 1_000;
+
+    /// A `mint_a` of the native mint means the deposit is held as lamports
+    /// in the escrow account itself rather than in a token vault.
+    pub fn sells_sol(&self) -> bool {
+        self.mint_a == native_mint::ID
+    }
+
+    /// A `mint_b` of the native mint means takers pay the maker in lamports.
+    pub fn buys_sol(&self) -> bool {
+        self.mint_b == native_mint::ID
+    }
+
+    /// Lamports in the escrow beyond the deposit it holds for takers.
+    pub fn reclaimable_lamports(&self, lamports: u64) -> u64 {
+        if self.sells_sol() {
+            lamports.saturating_sub(self.deposit)
+        } else {
+            lamports
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    pub fn is_allowed_taker(&self, taker: &Pubkey, proof: &[[u8; 32]]) -> bool {
+        match self.allowed_takers {
+            None => true,
+            Some(AllowedTakers::Taker(allowed)) => allowed == *taker,
+            Some(AllowedTakers::MerkleRoot(root)) => {
+                let leaf = hashv(&[taker.as_ref()]).to_bytes();
+                let computed = proof.iter().fold(leaf, |node, sibling| {
+                    if node <= *sibling {
+                        hashv(&[&node, sibling]).to_bytes()
+                    } else {
+                        hashv(&[sibling, &node]).to_bytes()
+                    }
+                });
+                computed == root
+            }
+        }
+    }
+
+    /// Checks `amount` of the deposit can be filled right now.
+    pub fn ensure_fillable(&self, amount: u64, now: i64) -> Result<()> {
+        require!(!self.is_expired(now), EscrowError::OfferExpired);
+        require!(
+            amount > 0 && amount <= self.deposit,
+            EscrowError::InvalidAmount
+        );
+        require!(
+            amount >= self.min_fill || amount == self.deposit,
+            EscrowError::FillTooSmall
+        );
+        Ok(())
+    }
+
+    /// `mint_b` owed for filling `amount` of the remaining deposit, rounded
+    /// up so the maker never receives less than their asking price.
+    pub fn fill_cost(&self, amount: u64) -> Option<u64> {
+        if self.deposit == 0 {
+            return None;
+        }
+        (amount as u128 * self.receive as u128)
+            .div_ceil(self.deposit as u128)
+            .try_into()
+            .ok()
+    }
+}
+
+/// Open offers for a `(mint_a, mint_b)` pair, cheapest implied price
+/// (`receive` per unit of `deposit`) first, so wallets can find liquidity
+/// without scanning every escrow. Once the book is full, a new offer is only
+/// listed if it beats the worst one, and expired offers are pruned whenever
+/// the book changes.
+#[account]
+#[derive(InitSpace)]
+pub struct OfferBook {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    #[max_len(32)]
+    pub offers: Vec<BookEntry>,
+    /// Smallest listed deposit, in `mint_a` base units, so dust offers
+    /// can't crowd real liquidity out of the book
+    pub min_deposit: u64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct BookEntry {
+    pub escrow: Pubkey,
+    pub maker: Pubkey,
+    pub deposit: u64,
+    pub receive: u64,
+    pub expires_at: Option<i64>,
+}
+
+impl BookEntry {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Whether this offer asks for less `mint_b` per `mint_a` than `other`.
+    fn cheaper_than(&self, other: &BookEntry) -> bool {
+        (self.receive as u128 * other.deposit as u128)
+            < (other.receive as u128 * self.deposit as u128)
+    }
+}
+
+impl OfferBook {
+    /// Matches the `max_len` of `offers`
+    pub const MAX_OFFERS: usize = 32;
+    /// `min_deposit` is a thousandth of a whole `mint_a` token
+    pub const MIN_DEPOSIT_SCALE: u32 = 3;
+
+    pub fn min_deposit(decimals_a: u8) -> u64 {
+        10u64.saturating_pow(u32::from(decimals_a).saturating_sub(Self::MIN_DEPOSIT_SCALE))
+    }
+
+    /// Brings the listing for `escrow` in line with its current terms and
+    /// drops expired listings. Offers below `min_deposit`, expired offers,
+    /// offers restricted to chosen takers and oracle-priced offers, which
+    /// have no fixed price to rank, are delisted.
+    pub fn sync(&mut self, key: Pubkey, escrow: &Escrow, now: i64) {
+        self.offers.retain(|listed| listed.escrow != key && !listed.is_expired(now));
+        if escrow.deposit < self.min_deposit
+            || escrow.is_expired(now)
+            || escrow.allowed_takers.is_some()
+            || escrow.oracle_pricing.is_some()
+        {
+            return;
+        }
+
+        let entry = BookEntry {
+            escrow: key,
+            maker: escrow.maker,
+            deposit: escrow.deposit,
+            receive: escrow.receive,
+            expires_at: escrow.expires_at,
+        };
+        // Equal prices keep their listing order
+        let index = self
+            .offers
+            .iter()
+            .position(|listed| entry.cheaper_than(listed))
+            .unwrap_or(self.offers.len());
+        if index < Self::MAX_OFFERS {
+            self.offers.insert(index, entry);
+            self.offers.truncate(Self::MAX_OFFERS);
+        }
+    }
+
+    pub fn remove(&mut self, escrow: &Pubkey) {
+        self.offers.retain(|listed| listed.escrow != *escrow);
+    }
+}
+
+/// A prospective taker's proposal to fill `amount` of an escrow's deposit
+/// for `offer` of `mint_b`, held in the counter offer's own vault (or as
+/// lamports for native SOL) until the maker accepts or the taker cancels.
+#[account]
+#[derive(InitSpace)]
+pub struct CounterOffer {
+    pub escrow: Pubkey,
+    pub taker: Pubkey,
+    pub mint_b: Pubkey,
+    pub amount: u64,
+    pub offer: u64,
+    pub bump: u8,
+}
+
+impl CounterOffer {
+    /// A `mint_b` of the native mint means the offer is held as lamports in
+    /// the counter offer account itself.
+    pub fn pays_sol(&self) -> bool {
+        self.mint_b == native_mint::ID
+    }
+}
+
+/// Buyer-funded escrow for off-chain goods. Funds go to the seller when the
+/// buyer confirms or the dispute window lapses after delivery; once
+/// disputed, only `arbiter` can settle them.
+#[account]
+#[derive(InitSpace)]
+pub struct ArbitratedEscrow {
+    pub seed: u64,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub arbiter: Pubkey,
+    pub mint: Pubkey,
+    /// Seconds after delivery during which the buyer may dispute
+    pub dispute_window: i64,
+    pub status: DealStatus,
+    pub delivered_at: i64,
+    pub disputed_at: i64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum DealStatus {
+    Funded,
+    Delivered,
+    Disputed,
+}
+
+impl ArbitratedEscrow {
+    /// Seconds the arbiter has to resolve a dispute before the buyer may
+    /// take the funds back.
+    pub const ARBITRATION_PERIOD: i64 = 30 * 24 * 60 * 60;
+
+    /// The buyer may dispute before delivery and within the window after it.
+    pub fn can_dispute(&self, now: i64) -> bool {
+        match self.status {
+            DealStatus::Funded => true,
+            DealStatus::Delivered => now < self.delivered_at.saturating_add(self.dispute_window),
+            DealStatus::Disputed => false,
+        }
+    }
+
+    /// A dispute the arbiter has left unresolved for `ARBITRATION_PERIOD`.
+    pub fn arbitration_lapsed(&self, now: i64) -> bool {
+        self.status == DealStatus::Disputed
+            && now >= self.disputed_at.saturating_add(Self::ARBITRATION_PERIOD)
+    }
+}
+
+/// Staged payment from `payer` to `payee`. Milestones are submitted and
+/// released strictly in order; after the final deadline, milestones the
+/// payee submitted in time are paid out and the rest refunded to the payer.
+#[account]
+#[derive(InitSpace)]
+pub struct MilestoneEscrow {
+    pub seed: u64,
+    pub payer: Pubkey,
+    pub payee: Pubkey,
+    pub mint: Pubkey,
+    #[max_len(10)]
+    pub milestones: Vec<Milestone>,
+    /// Milestones the payee has marked complete
+    pub submitted: u8,
+    /// Milestones the payer has paid out
+    pub released: u8,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct Milestone {
+    pub amount: u64,
+    pub description_hash: [u8; 32],
+    pub deadline: i64,
+}
+
+impl MilestoneEscrow {
+    /// Matches the `max_len` of `milestones`
+    pub const MAX_MILESTONES: usize = 10;
+
+    pub fn valid_milestones(milestones: &[Milestone], now: i64) -> bool {
+        !milestones.is_empty()
+            && milestones.len() <= Self::MAX_MILESTONES
+            && milestones.iter().all(|milestone| milestone.amount > 0)
+            && milestones[0].deadline > now
+            && milestones
+                .windows(2)
+                .all(|pair| pair[0].deadline <= pair[1].deadline)
+    }
+
+    pub fn total(&self) -> Option<u64> {
+        self.milestones
+            .iter()
+            .try_fold(0u64, |total, milestone| total.checked_add(milestone.amount))
+    }
+
+    pub fn final_deadline(&self) -> i64 {
+        self.milestones.last().map_or(0, |milestone| milestone.deadline)
+    }
+
+    /// Sum of the milestones submitted but not yet paid out. Each was
+    /// submitted before its deadline, so the payee is owed it.
+    pub fn submitted_unreleased(&self) -> u64 {
+        self.milestones[self.released as usize..self.submitted as usize]
+            .iter()
+            .map(|milestone| milestone.amount)
+            .sum()
+    }
+}
+
+/// Program-wide settings. Protocol fees collect in ATAs owned by the
+/// `treasury` PDA, or as lamports in the PDA itself for native SOL.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowConfig {
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+    pub config_bump: u8,
+    pub treasury_bump: u8,
+}
+
+impl EscrowConfig {
+    /// Hard cap on `fee_bps` that no admin update can exceed
+    pub const MAX_FEE_BPS: u16 = 500;
+
+    /// Fee skimmed from `amount` of the `mint_b` leg, rounded down.
+    pub fn protocol_fee(&self, amount: u64) -> u64 {
+        (amount as u128 * self.fee_bps as u128 / 10_000) as u64
+    }
+}
+
+/// A multi-asset offer: every `offered` leg sits in a bundle-owned ATA until
+/// a taker pays every `requested` leg in the same transaction.
+#[account]
+#[derive(InitSpace)]
+pub struct Bundle {
+    pub seed: u64,
+    pub maker: Pubkey,
+    #[max_len(5)]
+    pub offered: Vec<BundleLeg>,
+    #[max_len(5)]
+    pub requested: Vec<BundleLeg>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct BundleLeg {
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+impl Bundle {
+    /// Matches the `max_len` of `offered` and `requested`
+    pub const MAX_LEGS: usize = 5;
+
+    /// Legs must be non-empty, bounded, non-zero and use each mint once.
+    pub fn valid_legs(legs: &[BundleLeg]) -> bool {
+        !legs.is_empty()
+            && legs.len() <= Self::MAX_LEGS
+            && legs.iter().enumerate().all(|(i, leg)| {
+                leg.amount > 0 && legs[..i].iter().all(|other| other.mint != leg.mint)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escrow(receive: u64, deposit: u64) -> Escrow {
+        Escrow {
+            seed: 0,
+            maker: Pubkey::new_unique(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            receive,
+            receive_mode: ReceiveMode::Gross,
+            deposit,
+            min_fill: 0,
+            expires_at: None,
+            allowed_takers: None,
+            oracle_pricing: None,
+            bump: 0,
+        }
+    }
+
+    /// Fills `amount` the way `take` does, returning its cost.
+    fn fill(escrow: &mut Escrow, amount: u64) -> u64 {
+        let cost = escrow.fill_cost(amount).unwrap();
+        escrow.deposit -= amount;
+        escrow.receive = escrow.receive.saturating_sub(cost);
+        cost
+    }
+
+    fn leaf(taker: &Pubkey) -> [u8; 32] {
+        hashv(&[taker.as_ref()]).to_bytes()
+    }
+
+    fn node(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        hashv(&[&low, &high]).to_bytes()
+    }
+
+    #[test]
+    fn open_and_single_taker_offers() {
+        let taker = Pubkey::new_unique();
+        let mut escrow = escrow(5_000, 1_000);
+        assert!(escrow.is_allowed_taker(&taker, &[]));
+
+        escrow.allowed_takers = Some(AllowedTakers::Taker(taker));
+        assert!(escrow.is_allowed_taker(&taker, &[]));
+        assert!(!escrow.is_allowed_taker(&Pubkey::new_unique(), &[]));
+    }
+
+    #[test]
+    fn merkle_proofs_admit_every_listed_taker() {
+        let takers: [Pubkey; 3] = std::array::from_fn(|_| Pubkey::new_unique());
+        let [a, b, c] = takers.map(|taker| leaf(&taker));
+        let ab = node(a, b);
+        let mut escrow = escrow(5_000, 1_000);
+        escrow.allowed_takers = Some(AllowedTakers::MerkleRoot(node(ab, c)));
+
+        assert!(escrow.is_allowed_taker(&takers[0], &[b, c]));
+        assert!(escrow.is_allowed_taker(&takers[1], &[a, c]));
+        assert!(escrow.is_allowed_taker(&takers[2], &[ab]));
+    }
+
+    #[test]
+    fn merkle_proofs_reject_outsiders_and_bad_proofs() {
+        let takers: [Pubkey; 3] = std::array::from_fn(|_| Pubkey::new_unique());
+        let [a, b, c] = takers.map(|taker| leaf(&taker));
+        let mut escrow = escrow(5_000, 1_000);
+        escrow.allowed_takers = Some(AllowedTakers::MerkleRoot(node(node(a, b), c)));
+
+        // Someone else replaying a listed taker's proof
+        assert!(!escrow.is_allowed_taker(&Pubkey::new_unique(), &[b, c]));
+        // A proof with a sibling swapped out, or cut short
+        assert!(!escrow.is_allowed_taker(&takers[0], &[c, b]));
+        assert!(!escrow.is_allowed_taker(&takers[0], &[b]));
+        // Nor can a listed taker skip the proof
+        assert!(!escrow.is_allowed_taker(&takers[0], &[]));
+    }
+
+    #[test]
+    fn empty_proof_only_fits_a_single_leaf_tree() {
+        let taker = Pubkey::new_unique();
+        let mut escrow = escrow(5_000, 1_000);
+        escrow.allowed_takers = Some(AllowedTakers::MerkleRoot(leaf(&taker)));
+
+        assert!(escrow.is_allowed_taker(&taker, &[]));
+        assert!(!escrow.is_allowed_taker(&Pubkey::new_unique(), &[]));
+    }
+
+    #[test]
+    fn fill_cost_is_pro_rata() {
+        let escrow = escrow(5_000, 1_000);
+        assert_eq!(escrow.fill_cost(400), Some(2_000));
+        assert_eq!(escrow.fill_cost(1_000), Some(5_000));
+    }
+
+    #[test]
+    fn fill_cost_rounds_up_for_the_maker() {
+        assert_eq!(escrow(10, 3).fill_cost(1), Some(4));
+        assert_eq!(escrow(10, 3).fill_cost(2), Some(7));
+        // Dust fills of a cheap asset still cost at least one base unit
+        assert_eq!(escrow(1, 1_000).fill_cost(1), Some(1));
+    }
+
+    #[test]
+    fn last_fill_pays_whatever_is_still_owed() {
+        let mut escrow = escrow(5_000, 1_000);
+        assert_eq!(fill(&mut escrow, 400), 2_000);
+        assert_eq!(fill(&mut escrow, 600), 3_000);
+        assert_eq!(escrow.deposit, 0);
+        assert_eq!(escrow.receive, 0);
+    }
+
+    #[test]
+    fn partial_fills_never_pay_less_than_the_asking_price() {
+        let mut escrow = escrow(10, 3);
+        let paid: u64 = (0..3).map(|_| fill(&mut escrow, 1)).sum();
+        assert_eq!(paid, 10);
+        assert_eq!(escrow.receive, 0);
+    }
+
+    #[test]
+    fn fill_cost_rejects_empty_deposits_and_overflow() {
+        assert_eq!(escrow(5_000, 0).fill_cost(1), None);
+        assert_eq!(escrow(u64::MAX, 1).fill_cost(2), None);
+    }
+
+    fn book() -> OfferBook {
+        OfferBook {
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            offers: vec![],
+            min_deposit: OfferBook::min_deposit(6),
+            bump: 0,
+        }
+    }
+
+    fn listed(book: &OfferBook) -> Vec<Pubkey> {
+        book.offers.iter().map(|entry| entry.escrow).collect()
+    }
+
+    #[test]
+    fn offer_book_ranks_by_implied_price() {
+        let mut book = book();
+        let (dear, cheap, also_cheap) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        book.sync(dear, &escrow(30_000, 10_000), 0);
+        book.sync(cheap, &escrow(20_000, 10_000), 0);
+        // Same price as `cheap`, so it lists behind it
+        book.sync(also_cheap, &escrow(40_000, 20_000), 0);
+        assert_eq!(listed(&book), vec![cheap, also_cheap, dear]);
+    }
+
+    #[test]
+    fn offer_book_relists_on_new_terms() {
+        let mut book = book();
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        book.sync(first, &escrow(20_000, 10_000), 0);
+        book.sync(second, &escrow(30_000, 10_000), 0);
+
+        book.sync(first, &escrow(40_000, 10_000), 0);
+        assert_eq!(listed(&book), vec![second, first]);
+    }
+
+    #[test]
+    fn full_offer_book_keeps_the_best_offers() {
+        let mut book = book();
+        for i in 0..OfferBook::MAX_OFFERS as u64 {
+            book.sync(Pubkey::new_unique(), &escrow(20_000 + i, 10_000), 0);
+        }
+        let worst = *listed(&book).last().unwrap();
+
+        let too_dear = Pubkey::new_unique();
+        book.sync(too_dear, &escrow(50_000, 10_000), 0);
+        assert!(!listed(&book).contains(&too_dear));
+
+        let best = Pubkey::new_unique();
+        book.sync(best, &escrow(10_000, 10_000), 0);
+        assert_eq!(book.offers.len(), OfferBook::MAX_OFFERS);
+        assert_eq!(listed(&book)[0], best);
+        assert!(!listed(&book).contains(&worst));
+    }
+
+    #[test]
+    fn offer_book_minimum_scales_with_decimals() {
+        assert_eq!(OfferBook::min_deposit(9), 1_000_000);
+        assert_eq!(OfferBook::min_deposit(6), 1_000);
+        assert_eq!(OfferBook::min_deposit(3), 1);
+        assert_eq!(OfferBook::min_deposit(0), 1);
+        assert_eq!(OfferBook::min_deposit(u8::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn offer_book_delists_unrankable_offers() {
+        let mut book = book();
+        let key = Pubkey::new_unique();
+        book.sync(key, &escrow(20_000, 10_000), 0);
+
+        book.sync(key, &escrow(20_000, 0), 0);
+        assert!(book.offers.is_empty());
+
+        book.sync(key, &escrow(20_000, book.min_deposit - 1), 0);
+        assert!(book.offers.is_empty());
+
+        let mut restricted = escrow(20_000, 10_000);
+        restricted.allowed_takers = Some(AllowedTakers::Taker(Pubkey::new_unique()));
+        book.sync(key, &restricted, 0);
+        assert!(book.offers.is_empty());
+
+        let mut expired = escrow(20_000, 10_000);
+        expired.expires_at = Some(100);
+        book.sync(key, &expired, 100);
+        assert!(book.offers.is_empty());
+    }
+
+    #[test]
+    fn offer_book_prunes_expired_listings() {
+        let mut book = book();
+        let (expiring, open) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut offer = escrow(20_000, 10_000);
+        offer.expires_at = Some(100);
+        book.sync(expiring, &offer, 50);
+        assert_eq!(listed(&book), vec![expiring]);
+
+        // Any later change to the book drops it once it has expired
+        book.sync(open, &escrow(30_000, 10_000), 100);
+        assert_eq!(listed(&book), vec![open]);
+    }
+
+    fn deal(status: DealStatus) -> ArbitratedEscrow {
+        ArbitratedEscrow {
+            seed: 0,
+            buyer: Pubkey::new_unique(),
+            seller: Pubkey::new_unique(),
+            arbiter: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            dispute_window: 100,
+            status,
+            delivered_at: 1_000,
+            disputed_at: 1_000,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn disputes_open_until_the_window_after_delivery_lapses() {
+        assert!(deal(DealStatus::Funded).can_dispute(i64::MAX));
+
+        let delivered = deal(DealStatus::Delivered);
+        assert!(delivered.can_dispute(1_000));
+        assert!(delivered.can_dispute(1_099));
+        assert!(!delivered.can_dispute(1_100));
+    }
+
+    #[test]
+    fn a_dispute_cannot_be_reopened() {
+        let disputed = deal(DealStatus::Disputed);
+        assert!(!disputed.can_dispute(0));
+        assert!(!disputed.can_dispute(1_050));
+    }
+
+    #[test]
+    fn arbitration_lapses_after_its_period() {
+        let lapse = 1_000 + ArbitratedEscrow::ARBITRATION_PERIOD;
+        let disputed = deal(DealStatus::Disputed);
+        assert!(!disputed.arbitration_lapsed(lapse - 1));
+        assert!(disputed.arbitration_lapsed(lapse));
+
+        assert!(!deal(DealStatus::Delivered).arbitration_lapsed(lapse));
+    }
+
+    fn milestone(amount: u64, deadline: i64) -> Milestone {
+        Milestone {
+            amount,
+            description_hash: [0; 32],
+            deadline,
+        }
+    }
+
+    fn milestones(milestones: Vec<Milestone>) -> MilestoneEscrow {
+        MilestoneEscrow {
+            seed: 0,
+            payer: Pubkey::new_unique(),
+            payee: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            milestones,
+            submitted: 0,
+            released: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn milestones_need_future_deadlines_in_order() {
+        let valid = [milestone(100, 200), milestone(50, 200), milestone(25, 300)];
+        assert!(MilestoneEscrow::valid_milestones(&valid, 199));
+        // The first deadline, and so every deadline, must still be ahead
+        assert!(!MilestoneEscrow::valid_milestones(&valid, 200));
+
+        let unordered = [milestone(100, 300), milestone(50, 200)];
+        assert!(!MilestoneEscrow::valid_milestones(&unordered, 0));
+    }
+
+    #[test]
+    fn milestones_must_be_non_empty_bounded_and_non_zero() {
+        assert!(!MilestoneEscrow::valid_milestones(&[], 0));
+        assert!(!MilestoneEscrow::valid_milestones(
+            &[milestone(100, 200), milestone(0, 300)],
+            0
+        ));
+
+        let most = vec![milestone(1, 200); MilestoneEscrow::MAX_MILESTONES];
+        assert!(MilestoneEscrow::valid_milestones(&most, 0));
+        let too_many = vec![milestone(1, 200); MilestoneEscrow::MAX_MILESTONES + 1];
+        assert!(!MilestoneEscrow::valid_milestones(&too_many, 0));
+    }
+
+    #[test]
+    fn milestone_total_and_final_deadline() {
+        let escrow = milestones(vec![milestone(100, 200), milestone(50, 300)]);
+        assert_eq!(escrow.total(), Some(150));
+        assert_eq!(escrow.final_deadline(), 300);
+
+        let overflowing = milestones(vec![milestone(u64::MAX, 200), milestone(1, 300)]);
+        assert_eq!(overflowing.total(), None);
+    }
+
+    #[test]
+    fn submitted_unreleased_counts_milestones_awaiting_approval() {
+        let mut escrow = milestones(vec![
+            milestone(100, 200),
+            milestone(50, 300),
+            milestone(25, 400),
+        ]);
+        assert_eq!(escrow.submitted_unreleased(), 0);
+
+        escrow.submitted = 2;
+        assert_eq!(escrow.submitted_unreleased(), 150);
+        escrow.released = 1;
+        assert_eq!(escrow.submitted_unreleased(), 50);
+        escrow.released = 2;
+        assert_eq!(escrow.submitted_unreleased(), 0);
+    }
+
+    fn leg(mint: Pubkey, amount: u64) -> BundleLeg {
+        BundleLeg { mint, amount }
+    }
+
+    #[test]
+    fn bundle_legs_must_be_non_empty_bounded_and_non_zero() {
+        assert!(Bundle::valid_legs(&[leg(Pubkey::new_unique(), 1)]));
+        assert!(!Bundle::valid_legs(&[]));
+        assert!(!Bundle::valid_legs(&[
+            leg(Pubkey::new_unique(), 1),
+            leg(Pubkey::new_unique(), 0),
+        ]));
+
+        let max: Vec<_> = (0..Bundle::MAX_LEGS)
+            .map(|_| leg(Pubkey::new_unique(), 1))
+            .collect();
+        assert!(Bundle::valid_legs(&max));
+        let too_many: Vec<_> = (0..=Bundle::MAX_LEGS)
+            .map(|_| leg(Pubkey::new_unique(), 1))
+            .collect();
+        assert!(!Bundle::valid_legs(&too_many));
+    }
+
+    #[test]
+    fn bundle_legs_use_each_mint_once() {
+        let mint = Pubkey::new_unique();
+        assert!(!Bundle::valid_legs(&[
+            leg(mint, 1),
+            leg(Pubkey::new_unique(), 2),
+            leg(mint, 3),
+        ]));
+    }
+}
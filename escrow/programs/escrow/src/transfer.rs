@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::{extension::transfer_fee::TransferFeeConfig, onchain},
+    token_interface::{get_mint_extension_data, TransferChecked},
+};
+
+use crate::error::EscrowError;
+
+pub use token_fees::harvest_withheld_fees;
+
+/// Token-2022 transfer fee withheld when `amount` of `mint` is sent this
+/// epoch; zero for mints without the transfer-fee extension.
+pub fn transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let Ok(config) = get_mint_extension_data::<TransferFeeConfig>(mint) else {
+        return Ok(0);
+    };
+    let fee = config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(EscrowError::InvalidAmount)?;
+    Ok(fee)
+}
+
+/// Amount of `mint` to send so that `net` arrives after the transfer fee.
+pub fn gross_amount(mint: &AccountInfo, net: u64) -> Result<u64> {
+    let Ok(config) = get_mint_extension_data::<TransferFeeConfig>(mint) else {
+        return Ok(net);
+    };
+    let gross = config
+        .get_epoch_fee(Clock::get()?.epoch)
+        .calculate_pre_fee_amount(net)
+        .ok_or(EscrowError::InvalidAmount)?;
+    Ok(gross)
+}
+
+/// Drop-in for `token_interface::transfer_checked` that also resolves the
+/// mint's transfer-hook extra accounts from the context's remaining accounts.
+pub fn transfer_checked<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    onchain::invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        &ctx.remaining_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+
+declare_id!("AGfGiRXpUQTkENhGEgHwrSYYYFZb7CP1NrNQo81UNSUV");
+
+/// A price feed whose authority can set any price and publish time, for
+/// exercising oracle-priced escrows on a local validator.
+#[program]
+pub mod mock_oracle {
+    use super::*;
+
+    pub fn init_feed(ctx: Context<InitFeed>, price: i64, expo: i32) -> Result<()> {
+        ctx.accounts.feed.set_inner(PriceFeed {
+            authority: ctx.accounts.authority.key(),
+            price,
+            expo,
+            conf: 0,
+            publish_time: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// `publish_time` defaults to now; pass an older one to simulate a
+    /// stale feed.
+    pub fn set_price(
+        ctx: Context<SetPrice>,
+        price: i64,
+        expo: i32,
+        conf: u64,
+        publish_time: Option<i64>,
+    ) -> Result<()> {
+        let feed = &mut ctx.accounts.feed;
+        feed.price = price;
+        feed.expo = expo;
+        feed.conf = conf;
+        feed.publish_time = match publish_time {
+            Some(publish_time) => publish_time,
+            None => Clock::get()?.unix_timestamp,
+        };
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceFeed::INIT_SPACE
+    )]
+    pub feed: Account<'info, PriceFeed>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut, has_one = authority)]
+    pub feed: Account<'info, PriceFeed>,
+}
+
+/// The real price is `price * 10^expo` units of the quote per unit of the
+/// base asset, give or take `conf * 10^expo`.
+#[account]
+#[derive(InitSpace)]
+pub struct PriceFeed {
+    pub authority: Pubkey,
+    pub price: i64,
+    pub expo: i32,
+    pub conf: u64,
+    pub publish_time: i64,
+}
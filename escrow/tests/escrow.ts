@@ -1,16 +1,1410 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
+import {
+  createAssociatedTokenAccount,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  ExtensionType,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getMintLen,
+  getTransferFeeConfig,
+  mintTo,
+  NATIVE_MINT,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import {
+  AccountMeta,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+} from "@solana/web3.js";
+import { expect } from "chai";
 import { Escrow } from "../target/types/escrow";
+import { MockOracle } from "../target/types/mock_oracle";
 
 describe("escrow", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
 
   const program = anchor.workspace.escrow as Program<Escrow>;
+  const mockOracle = anchor.workspace.mockOracle as Program<MockOracle>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+  const connection = provider.connection;
+  const payer = (provider.wallet as anchor.Wallet).payer;
+  const maker = provider.wallet.publicKey;
+  const taker = Keypair.generate();
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+  const [treasury] = PublicKey.findProgramAddressSync(
+    [Buffer.from("treasury")],
+    program.programId
+  );
+
+  let mintA: PublicKey;
+  let mintB: PublicKey;
+
+  const escrowPda = (seed: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("escrow"),
+        maker.toBuffer(),
+        seed.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  const offerBook = (sold = mintA, bought = mintB) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("offer_book"), sold.toBuffer(), bought.toBuffer()],
+      program.programId
+    )[0];
+
+  const ata = (
+    mint: PublicKey,
+    owner: PublicKey,
+    tokenProgram = TOKEN_PROGRAM_ID
+  ) => getAssociatedTokenAddressSync(mint, owner, true, tokenProgram);
+
+  const balance = async (
+    mint: PublicKey,
+    owner: PublicKey,
+    tokenProgram = TOKEN_PROGRAM_ID
+  ) =>
+    Number(
+      (
+        await getAccount(
+          connection,
+          ata(mint, owner, tokenProgram),
+          undefined,
+          tokenProgram
+        )
+      ).amount
+    );
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    try {
+      await promise;
+      expect.fail(`expected ${code}`);
+    } catch (err) {
+      expect(err.error?.errorCode?.code).to.equal(code);
+    }
+  };
+
+  before(async () => {
+    await connection.confirmTransaction(
+      await connection.requestAirdrop(taker.publicKey, 10 * LAMPORTS_PER_SOL)
+    );
+
+    mintA = await createMint(connection, payer, maker, null, 6);
+    mintB = await createMint(connection, payer, maker, null, 6);
+    await createAssociatedTokenAccount(connection, payer, mintA, maker);
+    await createAssociatedTokenAccount(connection, payer, mintB, maker);
+    await createAssociatedTokenAccount(
+      connection,
+      payer,
+      mintA,
+      taker.publicKey
+    );
+    await createAssociatedTokenAccount(
+      connection,
+      payer,
+      mintB,
+      taker.publicKey
+    );
+    await mintTo(
+      connection,
+      payer,
+      mintA,
+      ata(mintA, maker),
+      maker,
+      10_000_000
+    );
+    await mintTo(
+      connection,
+      payer,
+      mintB,
+      ata(mintB, taker.publicKey),
+      maker,
+      100_000_000
+    );
+
+    // A 1% protocol fee
+    const [programData] = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    await program.methods
+      .initConfig(100)
+      .accountsPartial({ programData })
+      .rpc();
+  });
+
+  const make = (
+    seed: anchor.BN,
+    receive: number,
+    deposit: number,
+    oraclePricing = null
+  ) =>
+    program.methods
+      .make(
+        seed,
+        new anchor.BN(receive),
+        new anchor.BN(deposit),
+        new anchor.BN(0),
+        null,
+        null,
+        { gross: {} },
+        oraclePricing
+      )
+      .accountsPartial({
+        mintA,
+        mintB,
+        makerAtaA: ata(mintA, maker),
+        escrow: escrowPda(seed),
+        offerBook: offerBook(),
+        vault: ata(mintA, escrowPda(seed)),
+        tokenProgramA: TOKEN_PROGRAM_ID,
+        tokenProgramB: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  const take = (seed: anchor.BN, amount: number, oracle = null) =>
+    program.methods
+      .take(seed, new anchor.BN(amount), null, [])
+      .accountsPartial({
+        taker: taker.publicKey,
+        maker,
+        mintA,
+        mintB,
+        makerAtaB: ata(mintB, maker),
+        takerAtaB: ata(mintB, taker.publicKey),
+        takerAtaA: ata(mintA, taker.publicKey),
+        escrow: escrowPda(seed),
+        offerBook: offerBook(),
+        vault: ata(mintA, escrowPda(seed)),
+        treasuryAtaB: ata(mintB, treasury),
+        oracle,
+        tokenProgramA: TOKEN_PROGRAM_ID,
+        tokenProgramB: TOKEN_PROGRAM_ID,
+      })
+      .signers([taker])
+      .rpc();
+
+  const refund = (seed: anchor.BN) =>
+    program.methods
+      .refund()
+      .accountsPartial({
+        mintA,
+        makerAtaA: ata(mintA, maker),
+        escrow: escrowPda(seed),
+        offerBook: offerBook(),
+        vault: ata(mintA, escrowPda(seed)),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  it("Makes an offer", async () => {
+    const seed = new anchor.BN(1);
+    await make(seed, 5_000, 1_000);
+
+    const escrow = await program.account.escrow.fetch(escrowPda(seed));
+    expect(escrow.deposit.toNumber()).to.equal(1_000);
+    expect(escrow.receive.toNumber()).to.equal(5_000);
+    expect(await balance(mintA, escrowPda(seed))).to.equal(1_000);
+  });
+
+  it("Takes part of an offer, paying the maker less the protocol fee", async () => {
+    const seed = new anchor.BN(1);
+    await take(seed, 400);
+
+    // 400 of 1_000 costs 2_000, 20 of which goes to the treasury
+    expect(await balance(mintA, taker.publicKey)).to.equal(400);
+    expect(await balance(mintB, maker)).to.equal(1_980);
+    expect(await balance(mintB, treasury)).to.equal(20);
+
+    const escrow = await program.account.escrow.fetch(escrowPda(seed));
+    expect(escrow.deposit.toNumber()).to.equal(600);
+    expect(escrow.receive.toNumber()).to.equal(3_000);
+  });
+
+  it("Refunds the rest of a partly filled offer", async () => {
+    const seed = new anchor.BN(1);
+    const before = await balance(mintA, maker);
+    await refund(seed);
+
+    expect(await balance(mintA, maker)).to.equal(before + 600);
+    expect(await connection.getAccountInfo(escrowPda(seed))).to.be.null;
+    expect(await connection.getAccountInfo(ata(mintA, escrowPda(seed)))).to.be
+      .null;
+  });
+
+  it("Closes the escrow on the last fill", async () => {
+    const seed = new anchor.BN(2);
+    await make(seed, 500, 100);
+    await take(seed, 100);
+
+    expect(await balance(mintA, taker.publicKey)).to.equal(500);
+    expect(await connection.getAccountInfo(escrowPda(seed))).to.be.null;
+    expect(await connection.getAccountInfo(ata(mintA, escrowPda(seed)))).to.be
+      .null;
+  });
+
+  describe("oracle-priced offers", () => {
+    const seed = new anchor.BN(3);
+    const feed = Keypair.generate();
+
+    const setPrice = (
+      price: number,
+      publishTime: number | null = null,
+      conf = 0
+    ) =>
+      mockOracle.methods
+        .setPrice(
+          new anchor.BN(price),
+          -6,
+          new anchor.BN(conf),
+          publishTime === null ? null : new anchor.BN(publishTime)
+        )
+        .accounts({ feed: feed.publicKey })
+        .rpc();
+
+    before(async () => {
+      // 5 of `mint_b` per `mint_a`
+      await mockOracle.methods
+        .initFeed(new anchor.BN(5_000_000), -6)
+        .accounts({ feed: feed.publicKey })
+        .signers([feed])
+        .rpc();
+
+      // Sells at 1% over the feed, between 1 and 10 `mint_b` per `mint_a`,
+      // while the feed is within 0.5% of its price
+      await make(seed, 0, 1_000_000, {
+        oracle: feed.publicKey,
+        format: { mock: {} },
+        feedId: Array(32).fill(0),
+        spreadBps: 100,
+        maxStaleness: new anchor.BN(60),
+        maxConfBps: 50,
+        minPrice: new anchor.BN(1_000_000),
+        maxPrice: new anchor.BN(10_000_000),
+      });
+    });
+
+    it("Prices a fill off the feed plus the spread", async () => {
+      const before = await balance(mintB, maker);
+      await take(seed, 500_000, feed.publicKey);
+
+      // Half a `mint_a` at 5.05 costs 2_525_000, less the 1% protocol fee
+      expect(await balance(mintB, maker)).to.equal(before + 2_499_750);
+    });
+
+    it("Rejects a stale price", async () => {
+      await setPrice(5_000_000, Math.floor(Date.now() / 1000) - 3_600);
+      await expectError(take(seed, 100_000, feed.publicKey), "OracleStale");
+    });
+
+    it("Rejects a price with too wide a confidence interval", async () => {
+      await setPrice(5_000_000, null, 50_000);
+      await expectError(
+        take(seed, 100_000, feed.publicKey),
+        "OracleConfidenceTooWide"
+      );
+    });
+
+    it("Rejects a price outside the maker's bounds", async () => {
+      await setPrice(20_000_000);
+      await expectError(
+        take(seed, 100_000, feed.publicKey),
+        "OraclePriceOutOfBounds"
+      );
+    });
+  });
+
+  describe("arbitrated escrows", () => {
+    const seller = taker;
+    const arbiter = Keypair.generate();
+
+    const dealPda = (seed: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("arbitrated"),
+          maker.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const open = (seed: anchor.BN, disputeWindow: number) =>
+      program.methods
+        .openArbitrated(
+          seed,
+          seller.publicKey,
+          arbiter.publicKey,
+          new anchor.BN(1_000),
+          new anchor.BN(disputeWindow)
+        )
+        .accountsPartial({
+          mint: mintA,
+          buyerAta: ata(mintA, maker),
+          arbitratedEscrow: dealPda(seed),
+          vault: ata(mintA, dealPda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    const update = (seed: anchor.BN, signer: Keypair | null = null) => ({
+      signer: signer ? signer.publicKey : maker,
+      arbitratedEscrow: dealPda(seed),
+    });
+
+    const settle = (seed: anchor.BN, signer: Keypair | null = null) => ({
+      signer: signer ? signer.publicKey : maker,
+      buyer: maker,
+      seller: seller.publicKey,
+      mint: mintA,
+      buyerAta: ata(mintA, maker),
+      sellerAta: ata(mintA, seller.publicKey),
+      arbitratedEscrow: dealPda(seed),
+      vault: ata(mintA, dealPda(seed)),
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    before(async () => {
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(arbiter.publicKey, LAMPORTS_PER_SOL)
+      );
+    });
+
+    it("Releases to the seller when the buyer confirms delivery", async () => {
+      const seed = new anchor.BN(10);
+      const before = await balance(mintA, seller.publicKey);
+      await open(seed, 60);
+      await expectError(
+        program.methods
+          .confirmDelivery()
+          .accountsPartial({ ...settle(seed), sellerAta: null })
+          .rpc(),
+        "MissingTokenAccount"
+      );
+      // The buyer is paid nothing, so needs no token account
+      await program.methods
+        .confirmDelivery()
+        .accountsPartial({ ...settle(seed), buyerAta: null })
+        .rpc();
+
+      expect(await balance(mintA, seller.publicKey)).to.equal(before + 1_000);
+      expect(await connection.getAccountInfo(dealPda(seed))).to.be.null;
+    });
+
+    it("Lets anyone release a delivered deal once the window lapses", async () => {
+      const seed = new anchor.BN(11);
+      const before = await balance(mintA, seller.publicKey);
+      await open(seed, 1);
+      await program.methods
+        .markDelivered()
+        .accountsPartial(update(seed, seller))
+        .signers([seller])
+        .rpc();
+
+      await new Promise((resolve) => setTimeout(resolve, 3_000));
+      await program.methods
+        .releaseAfterWindow()
+        .accountsPartial(settle(seed, arbiter))
+        .signers([arbiter])
+        .rpc();
+
+      expect(await balance(mintA, seller.publicKey)).to.equal(before + 1_000);
+      expect(await connection.getAccountInfo(dealPda(seed))).to.be.null;
+    });
+
+    it("Leaves a disputed deal to the arbiter", async () => {
+      const seed = new anchor.BN(12);
+      const sellerBefore = await balance(mintA, seller.publicKey);
+      const buyerBefore = await balance(mintA, maker);
+      await open(seed, 60);
+      await program.methods
+        .openDispute()
+        .accountsPartial(update(seed))
+        .rpc();
+
+      await expectError(
+        program.methods
+          .confirmDelivery()
+          .accountsPartial(settle(seed))
+          .rpc(),
+        "InvalidStatus"
+      );
+      await expectError(
+        program.methods
+          .refundUnresolved()
+          .accountsPartial(settle(seed))
+          .rpc(),
+        "ArbitrationPending"
+      );
+
+      await program.methods
+        .resolveDispute(new anchor.BN(400))
+        .accountsPartial(settle(seed, arbiter))
+        .signers([arbiter])
+        .rpc();
+
+      expect(await balance(mintA, seller.publicKey)).to.equal(
+        sellerBefore + 400
+      );
+      expect(await balance(mintA, maker)).to.equal(buyerBefore - 400);
+      expect(await connection.getAccountInfo(dealPda(seed))).to.be.null;
+    });
+  });
+
+  describe("milestone escrows", () => {
+    const payee = taker;
+
+    const milestonePda = (seed: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("milestone"),
+          maker.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const milestone = (amount: number, deadline: number) => ({
+      amount: new anchor.BN(amount),
+      descriptionHash: Array(32).fill(0),
+      deadline: new anchor.BN(deadline),
+    });
+
+    const submit = (seed: anchor.BN) =>
+      program.methods
+        .submitMilestone()
+        .accountsPartial({
+          payee: payee.publicKey,
+          milestoneEscrow: milestonePda(seed),
+        })
+        .signers([payee])
+        .rpc();
+
+    const create = (
+      seed: anchor.BN,
+      milestones: ReturnType<typeof milestone>[]
+    ) =>
+      program.methods
+        .createMilestones(seed, payee.publicKey, milestones)
+        .accountsPartial({
+          mint: mintA,
+          payerAta: ata(mintA, maker),
+          milestoneEscrow: milestonePda(seed),
+          vault: ata(mintA, milestonePda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    const refund = (seed: anchor.BN, payeeAta: PublicKey | null = null) =>
+      program.methods
+        .refundMilestones()
+        .accountsPartial({
+          payee: payee.publicKey,
+          mint: mintA,
+          payerAta: ata(mintA, maker),
+          payeeAta,
+          milestoneEscrow: milestonePda(seed),
+          vault: ata(mintA, milestonePda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    it("Rejects a deadline that has already passed", async () => {
+      const now = Math.floor(Date.now() / 1000);
+      await expectError(
+        create(new anchor.BN(20), [milestone(300, now - 60)]),
+        "InvalidMilestones"
+      );
+    });
+
+    it("Pays out submitted milestones and refunds the rest after the deadline", async () => {
+      const seed = new anchor.BN(20);
+      const now = Math.floor(Date.now() / 1000);
+      const payerBefore = await balance(mintA, maker);
+      const payeeBefore = await balance(mintA, payee.publicKey);
+      await create(seed, [milestone(300, now + 6), milestone(700, now + 6)]);
+      expect(await balance(mintA, milestonePda(seed))).to.equal(1_000);
+
+      await submit(seed);
+      await program.methods
+        .approveMilestone()
+        .accountsPartial({
+          payee: payee.publicKey,
+          mint: mintA,
+          payeeAta: ata(mintA, payee.publicKey),
+          milestoneEscrow: milestonePda(seed),
+          vault: ata(mintA, milestonePda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      expect(await balance(mintA, payee.publicKey)).to.equal(payeeBefore + 300);
+
+      await new Promise((resolve) => setTimeout(resolve, 8_000));
+      await expectError(submit(seed), "MilestoneOverdue");
+
+      await refund(seed);
+
+      expect(await balance(mintA, maker)).to.equal(payerBefore - 300);
+      expect(await connection.getAccountInfo(milestonePda(seed))).to.be.null;
+    });
+
+    it("Pays the payee for a milestone submitted in time but never approved", async () => {
+      const seed = new anchor.BN(21);
+      const now = Math.floor(Date.now() / 1000);
+      const payerBefore = await balance(mintA, maker);
+      const payeeBefore = await balance(mintA, payee.publicKey);
+      await create(seed, [milestone(300, now + 6), milestone(700, now + 6)]);
+      await submit(seed);
+
+      await new Promise((resolve) => setTimeout(resolve, 8_000));
+      await expectError(refund(seed), "MissingTokenAccount");
+      await refund(seed, ata(mintA, payee.publicKey));
+
+      expect(await balance(mintA, payee.publicKey)).to.equal(payeeBefore + 300);
+      expect(await balance(mintA, maker)).to.equal(payerBefore - 300);
+      expect(await connection.getAccountInfo(milestonePda(seed))).to.be.null;
+    });
+  });
+
+  describe("bundle escrows", () => {
+    let nft: PublicKey;
+    let mint22: PublicKey;
+
+    const bundlePda = (seed: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("bundle"),
+          maker.toBuffer(),
+          seed.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    type Leg = { mint: PublicKey; amount: number; tokenProgram: PublicKey };
+
+    const args = (legs: Leg[]) =>
+      legs.map(({ mint, amount }) => ({ mint, amount: new anchor.BN(amount) }));
+
+    const meta = (pubkey: PublicKey, isWritable = true): AccountMeta => ({
+      pubkey,
+      isSigner: false,
+      isWritable,
+    });
+
+    // `[mint, from, to]` for each leg
+    const legAccounts = (legs: Leg[], from: PublicKey, to: PublicKey) =>
+      legs.flatMap(({ mint, tokenProgram }) => [
+        meta(mint),
+        meta(ata(mint, from, tokenProgram)),
+        meta(ata(mint, to, tokenProgram)),
+      ]);
+
+    const makeBundle = (
+      seed: anchor.BN,
+      offered: Leg[],
+      requested: Leg[],
+      accounts = legAccounts(offered, maker, bundlePda(seed))
+    ) =>
+      program.methods
+        .makeBundle(seed, args(offered), args(requested))
+        .accountsPartial({ bundle: bundlePda(seed) })
+        .remainingAccounts(accounts)
+        .rpc();
+
+    const takeBundle = (seed: anchor.BN, accounts: AccountMeta[]) =>
+      program.methods
+        .takeBundle()
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker,
+          bundle: bundlePda(seed),
+        })
+        .remainingAccounts(accounts)
+        .signers([taker])
+        .rpc();
+
+    const refundBundle = (seed: anchor.BN, accounts: AccountMeta[]) =>
+      program.methods
+        .refundBundle()
+        .accountsPartial({ bundle: bundlePda(seed) })
+        .remainingAccounts(accounts)
+        .rpc();
+
+    const offered = (): Leg[] => [
+      { mint: mintA, amount: 1_000, tokenProgram: TOKEN_PROGRAM_ID },
+      { mint: nft, amount: 1, tokenProgram: TOKEN_PROGRAM_ID },
+      { mint: mint22, amount: 500, tokenProgram: TOKEN_2022_PROGRAM_ID },
+    ];
+    const requested = (): Leg[] => [
+      { mint: mintB, amount: 5_000, tokenProgram: TOKEN_PROGRAM_ID },
+    ];
+
+    before(async () => {
+      nft = await createMint(connection, payer, maker, null, 0);
+      await createAssociatedTokenAccount(connection, payer, nft, maker);
+      await mintTo(connection, payer, nft, ata(nft, maker), maker, 1);
+
+      mint22 = await createMint(
+        connection,
+        payer,
+        maker,
+        null,
+        6,
+        Keypair.generate(),
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await createAssociatedTokenAccount(
+        connection,
+        payer,
+        mint22,
+        maker,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await mintTo(
+        connection,
+        payer,
+        mint22,
+        ata(mint22, maker, TOKEN_2022_PROGRAM_ID),
+        maker,
+        10_000,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+    });
+
+    it("Rejects legs whose accounts do not match", async () => {
+      const seed = new anchor.BN(30);
+      const legs = offered();
+      const accounts = legAccounts(legs, maker, bundlePda(seed));
+
+      const wrongMint = [...accounts];
+      wrongMint[3] = meta(mintB);
+      await expectError(
+        makeBundle(seed, legs, requested(), wrongMint),
+        "InvalidLegAccounts"
+      );
+
+      const wrongAta = [...accounts];
+      wrongAta[4] = meta(ata(nft, taker.publicKey));
+      await expectError(
+        makeBundle(seed, legs, requested(), wrongAta),
+        "InvalidLegAccounts"
+      );
+
+      await expectError(
+        makeBundle(seed, legs, requested(), accounts.slice(0, -1)),
+        "InvalidLegAccounts"
+      );
+    });
+
+    it("Takes every leg of a mixed SPL and Token-2022 bundle at once", async () => {
+      const seed = new anchor.BN(30);
+      const bundle = bundlePda(seed);
+      const makerBefore = await balance(mintB, maker);
+      const takerBefore = await balance(mintA, taker.publicKey);
+      await makeBundle(seed, offered(), requested());
+      expect(await balance(nft, bundle)).to.equal(1);
+      expect(await balance(mint22, bundle, TOKEN_2022_PROGRAM_ID)).to.equal(
+        500
+      );
+
+      const accounts = [
+        ...legAccounts(requested(), taker.publicKey, maker),
+        ...legAccounts(offered(), bundle, taker.publicKey),
+      ];
+      await expectError(
+        takeBundle(seed, accounts.slice(0, -1)),
+        "InvalidLegAccounts"
+      );
+      await takeBundle(seed, accounts);
+
+      expect(await balance(mintB, maker)).to.equal(makerBefore + 5_000);
+      expect(await balance(mintA, taker.publicKey)).to.equal(
+        takerBefore + 1_000
+      );
+      expect(await balance(nft, taker.publicKey)).to.equal(1);
+      expect(
+        await balance(mint22, taker.publicKey, TOKEN_2022_PROGRAM_ID)
+      ).to.equal(500);
+      expect(await connection.getAccountInfo(bundle)).to.be.null;
+      for (const { mint, tokenProgram } of offered()) {
+        const vault = ata(mint, bundle, tokenProgram);
+        expect(await connection.getAccountInfo(vault)).to.be.null;
+      }
+    });
+
+    it("Refunds every leg to the maker", async () => {
+      const seed = new anchor.BN(31);
+      const bundle = bundlePda(seed);
+      const legs = offered().filter(({ mint }) => !mint.equals(nft));
+      const mintABefore = await balance(mintA, maker);
+      const mint22Before = await balance(mint22, maker, TOKEN_2022_PROGRAM_ID);
+      await makeBundle(seed, legs, requested());
+      expect(await balance(mintA, maker)).to.equal(mintABefore - 1_000);
+
+      const accounts = legAccounts(legs, bundle, maker);
+      const swapped = [...accounts];
+      [swapped[1], swapped[2]] = [accounts[2], accounts[1]];
+      await expectError(refundBundle(seed, swapped), "InvalidLegAccounts");
+      await refundBundle(seed, accounts);
+
+      expect(await balance(mintA, maker)).to.equal(mintABefore);
+      expect(await balance(mint22, maker, TOKEN_2022_PROGRAM_ID)).to.equal(
+        mint22Before
+      );
+      expect(await connection.getAccountInfo(bundle)).to.be.null;
+    });
+  });
+
+  describe("native SOL offers", () => {
+    const lamports = (account: PublicKey) => connection.getBalance(account);
+
+    const makeOffer = (
+      seed: anchor.BN,
+      sold: PublicKey,
+      bought: PublicKey,
+      receive: number,
+      deposit: number,
+      vault: PublicKey | null
+    ) =>
+      program.methods
+        .make(
+          seed,
+          new anchor.BN(receive),
+          new anchor.BN(deposit),
+          new anchor.BN(0),
+          null,
+          null,
+          { gross: {} },
+          null
+        )
+        .accountsPartial({
+          mintA: sold,
+          mintB: bought,
+          makerAtaA: sold.equals(NATIVE_MINT) ? null : ata(sold, maker),
+          escrow: escrowPda(seed),
+          offerBook: offerBook(sold, bought),
+          vault,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    const takeOffer = (
+      seed: anchor.BN,
+      sold: PublicKey,
+      bought: PublicKey,
+      amount: number
+    ) => {
+      const sellsSol = sold.equals(NATIVE_MINT);
+      const buysSol = bought.equals(NATIVE_MINT);
+      return program.methods
+        .take(seed, new anchor.BN(amount), null, [])
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker,
+          mintA: sold,
+          mintB: bought,
+          makerAtaB: buysSol ? null : ata(bought, maker),
+          takerAtaB: buysSol ? null : ata(bought, taker.publicKey),
+          takerAtaA: sellsSol ? null : ata(sold, taker.publicKey),
+          escrow: escrowPda(seed),
+          offerBook: offerBook(sold, bought),
+          vault: sellsSol ? null : ata(sold, escrowPda(seed)),
+          treasuryAtaB: buysSol ? null : ata(bought, treasury),
+          oracle: null,
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .signers([taker])
+        .rpc();
+    };
+
+    it("Keeps a native SOL deposit out of token accounts", async () => {
+      const seed = new anchor.BN(40);
+      await expectError(
+        makeOffer(
+          seed,
+          NATIVE_MINT,
+          mintB,
+          10_000,
+          LAMPORTS_PER_SOL,
+          ata(NATIVE_MINT, escrowPda(seed))
+        ),
+        "UnexpectedTokenAccount"
+      );
+    });
+
+    it("Sells SOL for a token, holding the deposit as escrow lamports", async () => {
+      const seed = new anchor.BN(40);
+      const escrow = escrowPda(seed);
+      await makeOffer(seed, NATIVE_MINT, mintB, 10_000, LAMPORTS_PER_SOL, null);
+      const escrowBefore = await lamports(escrow);
+      const takerBefore = await lamports(taker.publicKey);
+      const makerBBefore = await balance(mintB, maker);
+
+      await takeOffer(seed, NATIVE_MINT, mintB, LAMPORTS_PER_SOL / 2);
+
+      // Half the SOL costs 5_000, 50 of which goes to the treasury
+      expect(await balance(mintB, maker)).to.equal(makerBBefore + 4_950);
+      expect(await lamports(escrow)).to.equal(
+        escrowBefore - LAMPORTS_PER_SOL / 2
+      );
+      expect(await lamports(taker.publicKey)).to.be.greaterThan(
+        takerBefore + LAMPORTS_PER_SOL / 2 - 10_000
+      );
+      const { deposit } = await program.account.escrow.fetch(escrow);
+      expect(deposit.toNumber()).to.equal(LAMPORTS_PER_SOL / 2);
+    });
+
+    it("Refunds the rest of a native SOL deposit with the escrow's rent", async () => {
+      const seed = new anchor.BN(40);
+      const escrow = escrowPda(seed);
+      const makerBefore = await lamports(maker);
+
+      await program.methods
+        .refund()
+        .accountsPartial({
+          mintA: NATIVE_MINT,
+          makerAtaA: null,
+          escrow,
+          offerBook: offerBook(NATIVE_MINT, mintB),
+          vault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      // The escrow's rent more than covers the fee for this transaction
+      expect(await lamports(maker)).to.be.greaterThan(
+        makerBefore + LAMPORTS_PER_SOL / 2
+      );
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+    });
+
+    it("Buys SOL with a token, paying the maker in lamports", async () => {
+      const seed = new anchor.BN(41);
+      const escrow = escrowPda(seed);
+      await makeOffer(
+        seed,
+        mintA,
+        NATIVE_MINT,
+        LAMPORTS_PER_SOL / 10,
+        1_000,
+        ata(mintA, escrow)
+      );
+      const makerBefore = await lamports(maker);
+      const treasuryBefore = await lamports(treasury);
+      const takerABefore = await balance(mintA, taker.publicKey);
+
+      await takeOffer(seed, mintA, NATIVE_MINT, 400);
+
+      // 400 of 1_000 costs 0.04 SOL, 1% of which goes to the treasury
+      expect(await lamports(maker)).to.equal(makerBefore + 39_600_000);
+      expect(await lamports(treasury)).to.equal(treasuryBefore + 400_000);
+      expect(await balance(mintA, taker.publicKey)).to.equal(
+        takerABefore + 400
+      );
+
+      await takeOffer(seed, mintA, NATIVE_MINT, 600);
+      expect(await lamports(maker)).to.be.greaterThan(
+        makerBefore + 39_600_000 + 59_400_000
+      );
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+      expect(await connection.getAccountInfo(ata(mintA, escrow))).to.be.null;
+    });
+  });
+
+  describe("transfer-fee mints", () => {
+    const tokenProgram = TOKEN_2022_PROGRAM_ID;
+    const seed = new anchor.BN(50);
+    let feeMintA: PublicKey;
+    let feeMintB: PublicKey;
+
+    // A Token-2022 mint withholding 1% of every transfer
+    const createFeeMint = async () => {
+      const mint = Keypair.generate();
+      const space = getMintLen([ExtensionType.TransferFeeConfig]);
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: maker,
+            newAccountPubkey: mint.publicKey,
+            space,
+            lamports: await connection.getMinimumBalanceForRentExemption(
+              space
+            ),
+            programId: tokenProgram,
+          }),
+          createInitializeTransferFeeConfigInstruction(
+            mint.publicKey,
+            maker,
+            maker,
+            100,
+            BigInt(1_000_000),
+            tokenProgram
+          ),
+          createInitializeMintInstruction(
+            mint.publicKey,
+            6,
+            maker,
+            null,
+            tokenProgram
+          )
+        ),
+        [mint]
+      );
+      for (const owner of [maker, taker.publicKey]) {
+        await createAssociatedTokenAccount(
+          connection,
+          payer,
+          mint.publicKey,
+          owner,
+          undefined,
+          tokenProgram
+        );
+      }
+      return mint.publicKey;
+    };
+
+    const setFee = (feeBps: number) =>
+      program.methods.setFee(feeBps).accountsPartial({ admin: maker }).rpc();
+
+    const takeFee = (amount: number) =>
+      program.methods
+        .take(seed, new anchor.BN(amount), null, [])
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker,
+          mintA: feeMintA,
+          mintB: feeMintB,
+          makerAtaB: ata(feeMintB, maker, tokenProgram),
+          takerAtaB: ata(feeMintB, taker.publicKey, tokenProgram),
+          takerAtaA: ata(feeMintA, taker.publicKey, tokenProgram),
+          escrow: escrowPda(seed),
+          offerBook: offerBook(feeMintA, feeMintB),
+          vault: ata(feeMintA, escrowPda(seed), tokenProgram),
+          treasuryAtaB: null,
+          oracle: null,
+          tokenProgramA: tokenProgram,
+          tokenProgramB: tokenProgram,
+        })
+        .signers([taker])
+        .rpc();
+
+    before(async () => {
+      feeMintA = await createFeeMint();
+      feeMintB = await createFeeMint();
+      await mintTo(
+        connection,
+        payer,
+        feeMintA,
+        ata(feeMintA, maker, tokenProgram),
+        maker,
+        1_000_000,
+        [],
+        undefined,
+        tokenProgram
+      );
+      await mintTo(
+        connection,
+        payer,
+        feeMintB,
+        ata(feeMintB, taker.publicKey, tokenProgram),
+        maker,
+        1_000_000,
+        [],
+        undefined,
+        tokenProgram
+      );
+      // Without a protocol fee the maker is owed all of `receive`
+      await setFee(0);
+    });
+
+    after(() => setFee(100));
+
+    it("Pays a net-mode maker exactly what they asked for", async () => {
+      const escrow = escrowPda(seed);
+      const vault = ata(feeMintA, escrow, tokenProgram);
+      await program.methods
+        .make(
+          seed,
+          new anchor.BN(500_000),
+          new anchor.BN(1_000_000),
+          new anchor.BN(0),
+          null,
+          null,
+          { net: {} },
+          null
+        )
+        .accountsPartial({
+          mintA: feeMintA,
+          mintB: feeMintB,
+          makerAtaA: ata(feeMintA, maker, tokenProgram),
+          escrow,
+          offerBook: offerBook(feeMintA, feeMintB),
+          vault,
+          tokenProgramA: tokenProgram,
+          tokenProgramB: tokenProgram,
+        })
+        .rpc();
+
+      // 1% of the deposit is withheld on its way into the vault
+      const { deposit } = await program.account.escrow.fetch(escrow);
+      expect(deposit.toNumber()).to.equal(990_000);
+
+      await takeFee(396_000);
+      expect(await balance(feeMintB, maker, tokenProgram)).to.equal(200_000);
+
+      await takeFee(594_000);
+      expect(await balance(feeMintB, maker, tokenProgram)).to.equal(500_000);
+    });
+
+    it("Harvests the vault's withheld fees so it can close", async () => {
+      const escrow = escrowPda(seed);
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+      expect(
+        await connection.getAccountInfo(ata(feeMintA, escrow, tokenProgram))
+      ).to.be.null;
+
+      const mint = await getMint(connection, feeMintA, undefined, tokenProgram);
+      expect(Number(getTransferFeeConfig(mint).withheldAmount)).to.equal(
+        10_000
+      );
+    });
+  });
+
+  describe("counter offers", () => {
+    const counterPda = (seed: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("counter"),
+          escrowPda(seed).toBuffer(),
+          taker.publicKey.toBuffer(),
+        ],
+        program.programId
+      )[0];
+
+    // Sells 1_000 of mint A for `receive` of `bought`
+    const makeOffer = (
+      seed: anchor.BN,
+      bought: PublicKey,
+      receive: number,
+      allowedTakers = null
+    ) =>
+      program.methods
+        .make(
+          seed,
+          new anchor.BN(receive),
+          new anchor.BN(1_000),
+          new anchor.BN(0),
+          null,
+          allowedTakers,
+          { gross: {} },
+          null
+        )
+        .accountsPartial({
+          mintA,
+          mintB: bought,
+          makerAtaA: ata(mintA, maker),
+          escrow: escrowPda(seed),
+          offerBook: offerBook(mintA, bought),
+          vault: ata(mintA, escrowPda(seed)),
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    const makeCounter = (
+      seed: anchor.BN,
+      bought: PublicKey,
+      amount: number,
+      offer: number
+    ) => {
+      const paysSol = bought.equals(NATIVE_MINT);
+      return program.methods
+        .makeCounterOffer(new anchor.BN(amount), new anchor.BN(offer), [])
+        .accountsPartial({
+          taker: taker.publicKey,
+          maker,
+          mintB: bought,
+          takerAtaB: paysSol ? null : ata(bought, taker.publicKey),
+          escrow: escrowPda(seed),
+          counterOffer: counterPda(seed),
+          counterVault: paysSol ? null : ata(bought, counterPda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([taker])
+        .rpc();
+    };
+
+    const cancelCounter = (seed: anchor.BN, bought: PublicKey) => {
+      const paysSol = bought.equals(NATIVE_MINT);
+      return program.methods
+        .cancelCounterOffer()
+        .accountsPartial({
+          taker: taker.publicKey,
+          mintB: bought,
+          takerAtaB: paysSol ? null : ata(bought, taker.publicKey),
+          counterOffer: counterPda(seed),
+          counterVault: paysSol ? null : ata(bought, counterPda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([taker])
+        .rpc();
+    };
+
+    const acceptCounter = (seed: anchor.BN, bought: PublicKey) => {
+      const paysSol = bought.equals(NATIVE_MINT);
+      return program.methods
+        .acceptCounterOffer()
+        .accountsPartial({
+          taker: taker.publicKey,
+          mintA,
+          mintB: bought,
+          escrow: escrowPda(seed),
+          offerBook: offerBook(mintA, bought),
+          vault: ata(mintA, escrowPda(seed)),
+          counterOffer: counterPda(seed),
+          counterVault: paysSol ? null : ata(bought, counterPda(seed)),
+          makerAtaB: paysSol ? null : ata(bought, maker),
+          takerAtaA: ata(mintA, taker.publicKey),
+          treasuryAtaB: paysSol ? null : ata(bought, treasury),
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+    };
+
+    it("Rejects a counter offer from a taker the maker did not allow", async () => {
+      const seed = new anchor.BN(60);
+      await makeOffer(seed, mintB, 10_000, {
+        taker: { 0: Keypair.generate().publicKey },
+      });
+      await expectError(
+        makeCounter(seed, mintB, 1_000, 8_000),
+        "TakerNotAllowed"
+      );
+      await refund(seed);
+    });
+
+    it("Accepts a counter offer for part of the deposit", async () => {
+      const seed = new anchor.BN(61);
+      await makeOffer(seed, mintB, 10_000, { taker: { 0: taker.publicKey } });
+      const makerBBefore = await balance(mintB, maker);
+      const treasuryBefore = await balance(mintB, treasury);
+      const takerABefore = await balance(mintA, taker.publicKey);
+
+      // 400 of the deposit would cost 4_000 at the asking price
+      await makeCounter(seed, mintB, 400, 3_000);
+      await acceptCounter(seed, mintB);
+
+      expect(await balance(mintB, maker)).to.equal(makerBBefore + 2_970);
+      expect(await balance(mintB, treasury)).to.equal(treasuryBefore + 30);
+      expect(await balance(mintA, taker.publicKey)).to.equal(
+        takerABefore + 400
+      );
+      expect(await connection.getAccountInfo(counterPda(seed))).to.be.null;
+
+      // The rest of the offer keeps its asking price
+      const escrow = await program.account.escrow.fetch(escrowPda(seed));
+      expect(escrow.deposit.toNumber()).to.equal(600);
+      expect(escrow.receive.toNumber()).to.equal(6_000);
+    });
+
+    it("Returns a cancelled counter offer to the taker", async () => {
+      const seed = new anchor.BN(61);
+      const takerBBefore = await balance(mintB, taker.publicKey);
+
+      await makeCounter(seed, mintB, 600, 4_000);
+      expect(await balance(mintB, taker.publicKey)).to.equal(
+        takerBBefore - 4_000
+      );
+      await cancelCounter(seed, mintB);
+
+      expect(await balance(mintB, taker.publicKey)).to.equal(takerBBefore);
+      expect(await connection.getAccountInfo(counterPda(seed))).to.be.null;
+      expect(
+        await connection.getAccountInfo(ata(mintB, counterPda(seed)))
+      ).to.be.null;
+    });
+
+    it("Closes the escrow when a counter offer fills the rest", async () => {
+      const seed = new anchor.BN(61);
+      const makerBBefore = await balance(mintB, maker);
+
+      await makeCounter(seed, mintB, 600, 5_000);
+      await acceptCounter(seed, mintB);
+
+      const escrow = escrowPda(seed);
+      expect(await balance(mintB, maker)).to.equal(makerBBefore + 4_950);
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+      expect(await connection.getAccountInfo(ata(mintA, escrow))).to.be.null;
+    });
+
+    it("Holds a native SOL counter offer as lamports until cancelled", async () => {
+      const seed = new anchor.BN(62);
+      await makeOffer(seed, NATIVE_MINT, LAMPORTS_PER_SOL / 10);
+      const takerBefore = await connection.getBalance(taker.publicKey);
+
+      await makeCounter(seed, NATIVE_MINT, 500, LAMPORTS_PER_SOL / 25);
+      const counter = await program.account.counterOffer.fetch(
+        counterPda(seed)
+      );
+      expect(counter.offer.toNumber()).to.equal(LAMPORTS_PER_SOL / 25);
+      await cancelCounter(seed, NATIVE_MINT);
+
+      // Only transaction fees are lost
+      expect(await connection.getBalance(taker.publicKey)).to.be.greaterThan(
+        takerBefore - 20_000
+      );
+      expect(await connection.getAccountInfo(counterPda(seed))).to.be.null;
+    });
+
+    it("Pays the maker a native SOL counter offer for the whole deposit", async () => {
+      const seed = new anchor.BN(62);
+      const treasuryBefore = await connection.getBalance(treasury);
+      const takerABefore = await balance(mintA, taker.publicKey);
+
+      await makeCounter(seed, NATIVE_MINT, 1_000, LAMPORTS_PER_SOL / 20);
+      await acceptCounter(seed, NATIVE_MINT);
+
+      expect(await connection.getBalance(treasury)).to.equal(
+        treasuryBefore + LAMPORTS_PER_SOL / 2_000
+      );
+      expect(await balance(mintA, taker.publicKey)).to.equal(
+        takerABefore + 1_000
+      );
+      expect(await connection.getAccountInfo(counterPda(seed))).to.be.null;
+      expect(await connection.getAccountInfo(escrowPda(seed))).to.be.null;
+    });
+  });
+
+  describe("expiring offers", () => {
+    const cranker = Keypair.generate();
+
+    const makeExpiring = (
+      seed: anchor.BN,
+      sold: PublicKey,
+      deposit: number,
+      expiresAt: number
+    ) => {
+      const sellsSol = sold.equals(NATIVE_MINT);
+      return program.methods
+        .make(
+          seed,
+          new anchor.BN(10_000),
+          new anchor.BN(deposit),
+          new anchor.BN(0),
+          new anchor.BN(expiresAt),
+          null,
+          { gross: {} },
+          null
+        )
+        .accountsPartial({
+          mintA: sold,
+          mintB,
+          makerAtaA: sellsSol ? null : ata(sold, maker),
+          escrow: escrowPda(seed),
+          offerBook: offerBook(sold, mintB),
+          vault: sellsSol ? null : ata(sold, escrowPda(seed)),
+          tokenProgramA: TOKEN_PROGRAM_ID,
+          tokenProgramB: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+    };
+
+    const reclaim = (seed: anchor.BN, sold: PublicKey) => {
+      const sellsSol = sold.equals(NATIVE_MINT);
+      return program.methods
+        .reclaimExpired()
+        .accountsPartial({
+          cranker: cranker.publicKey,
+          maker,
+          mintA: sold,
+          makerAtaA: sellsSol ? null : ata(sold, maker),
+          escrow: escrowPda(seed),
+          offerBook: offerBook(sold, mintB),
+          vault: sellsSol ? null : ata(sold, escrowPda(seed)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([cranker])
+        .rpc();
+    };
+
+    const sleep = (ms: number) =>
+      new Promise((resolve) => setTimeout(resolve, ms));
+
+    before(async () => {
+      await connection.confirmTransaction(
+        await connection.requestAirdrop(cranker.publicKey, LAMPORTS_PER_SOL)
+      );
+    });
+
+    it("Refuses fills after expiry and pays whoever reclaims the offer", async () => {
+      const seed = new anchor.BN(70);
+      const escrow = escrowPda(seed);
+      const makerABefore = await balance(mintA, maker);
+      await makeExpiring(
+        seed,
+        mintA,
+        1_000,
+        Math.floor(Date.now() / 1000) + 4
+      );
+      await expectError(reclaim(seed, mintA), "OfferNotExpired");
+
+      await sleep(6_000);
+      await expectError(take(seed, 500), "OfferExpired");
+
+      // The cranker gets 10% of the escrow's rent; the maker pays the fee
+      const reward = Math.floor((await connection.getBalance(escrow)) / 10);
+      const crankerBefore = await connection.getBalance(cranker.publicKey);
+      await reclaim(seed, mintA);
+
+      expect(await connection.getBalance(cranker.publicKey)).to.equal(
+        crankerBefore + reward
+      );
+      expect(await balance(mintA, maker)).to.equal(makerABefore);
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+      expect(await connection.getAccountInfo(ata(mintA, escrow))).to.be.null;
+    });
+
+    it("Returns an expired SOL deposit without paying the cranker from it", async () => {
+      const seed = new anchor.BN(71);
+      const escrow = escrowPda(seed);
+      const deposit = LAMPORTS_PER_SOL / 2;
+      await makeExpiring(
+        seed,
+        NATIVE_MINT,
+        deposit,
+        Math.floor(Date.now() / 1000) + 4
+      );
+      await sleep(6_000);
+
+      const rent = (await connection.getBalance(escrow)) - deposit;
+      const crankerBefore = await connection.getBalance(cranker.publicKey);
+      const makerBefore = await connection.getBalance(maker);
+      await reclaim(seed, NATIVE_MINT);
+
+      expect(await connection.getBalance(cranker.publicKey)).to.equal(
+        crankerBefore + Math.floor(rent / 10)
+      );
+      expect(await connection.getBalance(maker)).to.be.greaterThan(
+        makerBefore + deposit
+      );
+      expect(await connection.getAccountInfo(escrow)).to.be.null;
+    });
   });
 });
@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig,
+    token_2022_extensions::transfer_fee::{
+        harvest_withheld_tokens_to_mint, HarvestWithheldTokensToMint,
+    },
+    token_interface::get_mint_extension_data,
+};
+
+/// Moves any Token-2022 transfer fees withheld in `account` to its mint.
+/// `close_account` fails while fees are withheld, so this runs before every
+/// token account is closed, and any context that closes one takes its mint
+/// as writable. Harvesting needs no authority; mints without the
+/// transfer-fee extension are left alone.
+pub fn harvest_withheld_fees<'info>(
+    token_program: &AccountInfo<'info>,
+    mint: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+) -> Result<()> {
+    if get_mint_extension_data::<TransferFeeConfig>(mint).is_err() {
+        return Ok(());
+    }
+
+    let cpi_accounts = HarvestWithheldTokensToMint {
+        token_program_id: token_program.clone(),
+        mint: mint.clone(),
+    };
+    let cpi_ctx = CpiContext::new(token_program.clone(), cpi_accounts);
+    harvest_withheld_tokens_to_mint(cpi_ctx, vec![account.clone()])
+}
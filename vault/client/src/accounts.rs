@@ -0,0 +1,160 @@
+use anchor_lang::AccountDeserialize;
+use vault::{UserVaults, VaultState};
+
+/// Decodes raw `VaultState` account data, checking the discriminator.
+pub fn decode_vault_state(mut data: &[u8]) -> anchor_lang::Result<VaultState> {
+    VaultState::try_deserialize(&mut data)
+}
+
+pub fn decode_user_vaults(mut data: &[u8]) -> anchor_lang::Result<UserVaults> {
+    UserVaults::try_deserialize(&mut data)
+}
+
+#[cfg(feature = "rpc")]
+pub use rpc::*;
+
+#[cfg(feature = "rpc")]
+mod rpc {
+    use std::fmt;
+
+    use anchor_lang::prelude::Pubkey;
+    use solana_client::client_error::ClientError;
+    use solana_client::rpc_client::RpcClient;
+    use vault::{UserVaults, VaultState};
+
+    use super::{decode_user_vaults, decode_vault_state};
+    use crate::pda::{user_vaults_address, vault_state_address};
+
+    #[derive(Debug)]
+    pub enum FetchError {
+        Rpc(Box<ClientError>),
+        Decode(anchor_lang::error::Error),
+    }
+
+    impl fmt::Display for FetchError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                FetchError::Rpc(err) => write!(f, "rpc error: {err}"),
+                FetchError::Decode(err) => write!(f, "decode error: {err}"),
+            }
+        }
+    }
+
+    impl std::error::Error for FetchError {}
+
+    impl From<ClientError> for FetchError {
+        fn from(err: ClientError) -> Self {
+            FetchError::Rpc(Box::new(err))
+        }
+    }
+
+    impl From<anchor_lang::error::Error> for FetchError {
+        fn from(err: anchor_lang::error::Error) -> Self {
+            FetchError::Decode(err)
+        }
+    }
+
+    pub fn fetch_vault_state(
+        rpc: &RpcClient,
+        owner: &Pubkey,
+        vault_id: u64,
+    ) -> Result<VaultState, FetchError> {
+        let data = rpc.get_account_data(&vault_state_address(owner, vault_id).0)?;
+        Ok(decode_vault_state(&data)?)
+    }
+
+    pub fn fetch_user_vaults(rpc: &RpcClient, owner: &Pubkey) -> Result<UserVaults, FetchError> {
+        let data = rpc.get_account_data(&user_vaults_address(owner).0)?;
+        Ok(decode_user_vaults(&data)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::prelude::Pubkey;
+    use anchor_lang::AccountSerialize;
+    use vault::state::{Lock, PendingRecovery};
+
+    use super::*;
+
+    fn vault_state() -> VaultState {
+        VaultState {
+            owner: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            vault_id: 3,
+            vault_bump: 254,
+            state_bump: 253,
+            token_mints: vec![Pubkey::new_unique()],
+            unlock_at: Some(1_700_000_000),
+            locks: vec![Lock {
+                amount: 500,
+                unlock_at: 1_800_000_000,
+            }],
+            guardians: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            threshold: 2,
+            withdraw_limit: 1_000,
+            approval_window_start: 1_650_000_000,
+            approval_window_withdrawn: 400,
+            token_limits: vec![],
+            proposal_count: 4,
+            open_proposals: 1,
+            rate_limit: None,
+            pending_rate_limit: None,
+            window_start: 0,
+            window_withdrawn: 0,
+            beneficiary: Some(Pubkey::new_unique()),
+            inactivity_period: 86_400,
+            last_active: 1_650_000_100,
+            stream_count: 2,
+            committed: 300,
+            stake_count: 1,
+            stake_accounts: 1,
+            allowances: 2,
+            recovery_keys: vec![Pubkey::new_unique()],
+            recovery_delay: 3_600,
+            pending_recovery: Some(PendingRecovery {
+                new_authority: Pubkey::new_unique(),
+                initiated_at: 1_650_000_200,
+            }),
+            total_deposited: 10_000,
+            total_withdrawn: 2_000,
+            created_at: 1_600_000_000,
+        }
+    }
+
+    #[test]
+    fn decodes_a_serialized_vault_state() {
+        let state = vault_state();
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+
+        let decoded = decode_vault_state(&data).unwrap();
+        assert_eq!(decoded.owner, state.owner);
+        assert_eq!(decoded.authority, state.authority);
+        assert_eq!(decoded.vault_id, state.vault_id);
+        assert_eq!(decoded.token_mints, state.token_mints);
+        assert_eq!(decoded.locks[0].amount, 500);
+        assert_eq!(decoded.guardians, state.guardians);
+        assert_eq!(decoded.open_proposals, 1);
+        assert_eq!(decoded.beneficiary, state.beneficiary);
+        assert_eq!(decoded.allowances, 2);
+        assert_eq!(
+            decoded.pending_recovery.unwrap().new_authority,
+            state.pending_recovery.unwrap().new_authority
+        );
+        assert_eq!(decoded.created_at, state.created_at);
+    }
+
+    #[test]
+    fn rejects_another_account_type() {
+        let user_vaults = UserVaults {
+            vault_ids: vec![3],
+            bump: 255,
+        };
+        let mut data = Vec::new();
+        user_vaults.try_serialize(&mut data).unwrap();
+
+        assert!(decode_vault_state(&data).is_err());
+        assert_eq!(decode_user_vaults(&data).unwrap().vault_ids, vec![3]);
+    }
+}
@@ -0,0 +1,146 @@
+//! Builders take the vault `owner` (the key its PDAs are derived from) and,
+//! where it can differ after a rotation, the signing `authority`.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+
+use crate::pda::{user_vaults_address, vault_address, vault_state_address};
+
+pub fn initialize(user: &Pubkey, vault_id: u64, unlock_at: Option<i64>) -> Instruction {
+    Instruction {
+        program_id: vault::ID,
+        accounts: vault::accounts::Initialize {
+            user: *user,
+            vault_state: vault_state_address(user, vault_id).0,
+            user_vaults: user_vaults_address(user).0,
+            vault: vault_address(user, vault_id).0,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: vault::instruction::Initialize { vault_id, unlock_at }.data(),
+    }
+}
+
+pub fn deposit(authority: &Pubkey, owner: &Pubkey, vault_id: u64, amount: u64) -> Instruction {
+    Instruction {
+        program_id: vault::ID,
+        accounts: payment_accounts(authority, owner, vault_id),
+        data: vault::instruction::Deposit { vault_id, amount }.data(),
+    }
+}
+
+pub fn withdraw(authority: &Pubkey, owner: &Pubkey, vault_id: u64, amount: u64) -> Instruction {
+    Instruction {
+        program_id: vault::ID,
+        accounts: payment_accounts(authority, owner, vault_id),
+        data: vault::instruction::Withdraw { vault_id, amount }.data(),
+    }
+}
+
+/// `token_accounts` are the vault's token accounts, which must all be empty.
+pub fn close(
+    authority: &Pubkey,
+    owner: &Pubkey,
+    vault_id: u64,
+    token_accounts: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vault::accounts::Close {
+        user: *authority,
+        vault_state: vault_state_address(owner, vault_id).0,
+        vault: vault_address(owner, vault_id).0,
+        user_vaults: user_vaults_address(owner).0,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        token_accounts
+            .iter()
+            .map(|account| AccountMeta::new_readonly(*account, false)),
+    );
+
+    Instruction {
+        program_id: vault::ID,
+        accounts,
+        data: vault::instruction::Close { vault_id }.data(),
+    }
+}
+
+fn payment_accounts(
+    authority: &Pubkey,
+    owner: &Pubkey,
+    vault_id: u64,
+) -> Vec<AccountMeta> {
+    vault::accounts::Payment {
+        user: *authority,
+        vault_state: vault_state_address(owner, vault_id).0,
+        vault: vault_address(owner, vault_id).0,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initialize_accounts_follow_the_program_order() {
+        let user = Pubkey::new_unique();
+        let ix = initialize(&user, 1, None);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                AccountMeta::new(user, true),
+                AccountMeta::new(vault_state_address(&user, 1).0, false),
+                AccountMeta::new(user_vaults_address(&user).0, false),
+                AccountMeta::new_readonly(vault_address(&user, 1).0, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn payment_accounts_sign_with_the_authority_but_derive_from_the_owner() {
+        let authority = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let expected = vec![
+            AccountMeta::new(authority, true),
+            AccountMeta::new(vault_state_address(&owner, 2).0, false),
+            AccountMeta::new(vault_address(&owner, 2).0, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ];
+        assert_eq!(deposit(&authority, &owner, 2, 10).accounts, expected);
+        assert_eq!(withdraw(&authority, &owner, 2, 10).accounts, expected);
+    }
+
+    #[test]
+    fn close_accounts_follow_the_program_order() {
+        let authority = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+        let ix = close(&authority, &owner, 2, &[token_account]);
+        assert_eq!(
+            ix.accounts,
+            vec![
+                AccountMeta::new(authority, true),
+                AccountMeta::new(vault_state_address(&owner, 2).0, false),
+                AccountMeta::new(vault_address(&owner, 2).0, false),
+                AccountMeta::new(user_vaults_address(&owner).0, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+                AccountMeta::new_readonly(token_account, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn data_starts_with_the_instruction_discriminator() {
+        use anchor_lang::Discriminator;
+
+        let ix = withdraw(&Pubkey::new_unique(), &Pubkey::new_unique(), 2, 10);
+        assert_eq!(ix.program_id, vault::ID);
+        assert_eq!(&ix.data[..8], vault::instruction::Withdraw::DISCRIMINATOR);
+        assert_eq!(&ix.data[8..16], 2u64.to_le_bytes().as_ref());
+        assert_eq!(&ix.data[16..], 10u64.to_le_bytes().as_ref());
+    }
+}
@@ -0,0 +1,12 @@
+//! Rust client for the `vault` program: PDA helpers, instruction builders and
+//! account decoding, so services don't have to hand-encode discriminators.
+//! The `rpc` feature adds account fetchers built on `solana-client`.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+
+pub use accounts::*;
+pub use instructions::*;
+pub use pda::*;
+pub use vault::{UserVaults, VaultState, ID as PROGRAM_ID};
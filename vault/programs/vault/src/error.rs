@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum VaultError {
+    #[msg("Token vaults must be emptied before closing the vault")]
+    TokenVaultsNotEmpty,
+    #[msg("The vault is time-locked")]
+    VaultLocked,
+    #[msg("Amount exceeds the unlocked vault balance")]
+    FundsLocked,
+    #[msg("Unlock time must be in the future and cannot move earlier")]
+    InvalidUnlockTime,
+    #[msg("Too many active deposit locks")]
+    TooManyLocks,
+    #[msg("Withdrawal exceeds the limit and needs guardian approval")]
+    ApprovalRequired,
+    #[msg("Invalid guardian set or threshold")]
+    InvalidGuardians,
+    #[msg("Not enough guardian approvals")]
+    ThresholdNotMet,
+    #[msg("Signer is not a guardian of this vault")]
+    NotGuardian,
+    #[msg("Guardian has already approved this proposal")]
+    AlreadyApproved,
+    #[msg("Withdrawal exceeds the remaining allowance for this window")]
+    RateLimitExceeded,
+    #[msg("Rate limit window must be greater than zero")]
+    InvalidRateLimit,
+    #[msg("Signer is not the vault beneficiary")]
+    NotBeneficiary,
+    #[msg("The owner has not been inactive for long enough")]
+    OwnerStillActive,
+    #[msg("Inactivity period is too short")]
+    InvalidInactivityPeriod,
+    #[msg("Too many vaults open for this user")]
+    TooManyVaults,
+    #[msg("Invalid allowance expiry or period")]
+    InvalidAllowance,
+    #[msg("Allowance has expired")]
+    AllowanceExpired,
+    #[msg("Amount exceeds the remaining allowance")]
+    AllowanceExceeded,
+    #[msg("Invalid stream schedule or rate")]
+    InvalidStream,
+    #[msg("Open streams must be settled before closing the vault")]
+    StreamsActive,
+    #[msg("Stake accounts must be withdrawn before closing the vault")]
+    StakeAccountsOpen,
+    #[msg("Signer is not the vault authority")]
+    Unauthorized,
+    #[msg("Invalid recovery keys or delay")]
+    InvalidRecovery,
+    #[msg("Signer is not a recovery key of this vault")]
+    NotRecoveryKey,
+    #[msg("No recovery is pending")]
+    NoPendingRecovery,
+    #[msg("The recovery timelock has not elapsed")]
+    RecoveryNotReady,
+    #[msg("Withdrawal would leave the vault below rent exemption")]
+    BelowRentExemption,
+    #[msg("Too many token mints held by the vault")]
+    TooManyTokenMints,
+    #[msg("Withdrawal proposals must be executed or cancelled before closing the vault")]
+    ProposalsOpen,
+    #[msg("Allowances must be revoked before closing the vault")]
+    AllowancesOpen,
+    #[msg("A recovery is already pending")]
+    RecoveryPending,
+    #[msg("Amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Token account is not held by this vault")]
+    NotVaultTokenAccount,
+}
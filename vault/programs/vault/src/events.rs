@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct VaultInitialized {
+    pub owner: Pubkey,
+    pub vault_id: u64,
+    pub created_at: i64,
+}
+
+#[event]
+pub struct Deposited {
+    pub owner: Pubkey,
+    pub vault_id: u64,
+    pub amount: u64,
+    pub balance: u64,
+    /// The token mint, or `None` for lamports
+    pub mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub owner: Pubkey,
+    pub vault_id: u64,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+    /// The token mint, or `None` for lamports
+    pub mint: Option<Pubkey>,
+}
+
+#[event]
+pub struct VaultClosed {
+    pub owner: Pubkey,
+    pub vault_id: u64,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
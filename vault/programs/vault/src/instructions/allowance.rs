@@ -0,0 +1,173 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::error::VaultError;
+use crate::events::Withdrawn;
+use crate::state::{Allowance, SpendPeriod, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, delegate: Pubkey)]
+pub struct ApproveAllowance<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"allowance", vault_state.key().as_ref(), delegate.as_ref()],
+        space = Allowance::INIT_SPACE,
+        bump
+    )]
+    pub allowance: Account<'info, Allowance>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct RevokeAllowance<'info> {
+    /// The authority, or the beneficiary of a claimable vault
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        has_one = vault_state,
+        close = user
+    )]
+    pub allowance: Account<'info, Allowance>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Spend<'info> {
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+    pub owner: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"allowance", vault_state.key().as_ref(), delegate.key().as_ref()],
+        bump = allowance.bump,
+    )]
+    pub allowance: Account<'info, Allowance>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ApproveAllowance<'info> {
+    /// Grants (or re-grants, resetting what was spent) an allowance to `delegate`.
+    pub fn approve_allowance(
+        &mut self,
+        delegate: Pubkey,
+        max_amount: u64,
+        expires_at: Option<i64>,
+        period: Option<SpendPeriod>,
+        bumps: &ApproveAllowanceBumps,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(expires_at) = expires_at {
+            require!(expires_at > now, VaultError::InvalidAllowance);
+        }
+        if let Some(period) = period {
+            require!(period.length > 0, VaultError::InvalidAllowance);
+        }
+
+        // `init_if_needed` leaves a fresh account zeroed
+        if self.allowance.vault_state == Pubkey::default() {
+            self.vault_state.allowances += 1;
+        }
+        self.allowance.set_inner(Allowance {
+            owner: self.vault_state.owner,
+            vault_state: self.vault_state.key(),
+            delegate,
+            max_amount,
+            spent: 0,
+            expires_at,
+            period,
+            period_start: now,
+            period_spent: 0,
+            bump: bumps.allowance,
+        });
+        self.vault_state.last_active = now;
+        Ok(())
+    }
+}
+
+impl<'info> RevokeAllowance<'info> {
+    pub fn revoke_allowance(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        self.vault_state
+            .ensure_can_wind_down(&self.user.key(), now)?;
+        self.vault_state.allowances = self.vault_state.allowances.saturating_sub(1);
+        if self.user.key() == self.vault_state.authority {
+            self.vault_state.last_active = now;
+        }
+        Ok(())
+    }
+}
+
+impl<'info> Spend<'info> {
+    pub fn spend(&mut self, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        self.allowance.consume(amount, clock.unix_timestamp)?;
+
+        self.vault_state.ensure_withdrawable(
+            amount,
+            self.vault.lamports(),
+            clock.unix_timestamp,
+        )?;
+        self.vault_state
+            .consume_withdraw_limit(amount, clock.unix_timestamp)?;
+        self.vault_state.consume_rate_limit(amount, &clock)?;
+        self.vault_state.prune_locks(clock.unix_timestamp);
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.delegate.to_account_info(),
+        };
+        let owner_key = self.owner.key();
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(amount);
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.delegate.key(),
+            amount,
+            balance: self.vault.lamports(),
+            mint: None,
+        });
+        Ok(())
+    }
+}
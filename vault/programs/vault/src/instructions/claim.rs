@@ -0,0 +1,214 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::error::VaultError;
+use crate::events::{VaultClosed, Withdrawn};
+use crate::instructions::{ensure_token_accounts_empty, harvest_withheld_fees};
+use crate::state::{UserVaults, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Claim<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    pub owner: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.beneficiary == Some(beneficiary.key()) @ VaultError::NotBeneficiary,
+        close = beneficiary
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"vaults", owner.key().as_ref()],
+        bump = user_vaults.bump
+    )]
+    pub user_vaults: Account<'info, UserVaults>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct ClaimSpl<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    pub owner: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.beneficiary == Some(beneficiary.key()) @ VaultError::NotBeneficiary,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        seeds = [b"vault", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = mint,
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program
+    )]
+    pub beneficiary_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_ata: InterfaceAccount<'info, TokenAccount>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Claim<'info> {
+    pub fn claim(&mut self, token_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            self.vault_state.is_claimable(now),
+            VaultError::OwnerStillActive
+        );
+        require!(
+            self.vault_state.token_mints.is_empty(),
+            VaultError::TokenVaultsNotEmpty
+        );
+        ensure_token_accounts_empty(&self.vault.key(), token_accounts)?;
+        require!(self.vault_state.committed == 0, VaultError::StreamsActive);
+        require!(
+            self.vault_state.open_proposals == 0,
+            VaultError::ProposalsOpen
+        );
+        require!(self.vault_state.allowances == 0, VaultError::AllowancesOpen);
+        require!(
+            self.vault_state.stake_accounts == 0,
+            VaultError::StakeAccountsOpen
+        );
+        require!(self.vault_state.is_unlocked(now), VaultError::VaultLocked);
+        require!(
+            self.vault_state.locked_amount(now) == 0,
+            VaultError::FundsLocked
+        );
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.beneficiary.to_account_info(),
+        };
+        let owner_key = self.owner.key();
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        let amount = self.vault.lamports();
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(amount);
+        emit!(VaultClosed {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.beneficiary.key(),
+            amount,
+        });
+
+        self.user_vaults.remove(self.vault_state.vault_id);
+        Ok(())
+    }
+}
+
+impl<'info> ClaimSpl<'info> {
+    pub fn claim_spl(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            self.vault_state.is_claimable(now),
+            VaultError::OwnerStillActive
+        );
+        require!(self.vault_state.is_unlocked(now), VaultError::VaultLocked);
+
+        let owner_key = self.owner.key();
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ]];
+
+        let balance = self.vault_ata.amount;
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.beneficiary_ata.to_account_info(),
+            authority: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, balance, self.mint.decimals)?;
+
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.beneficiary.key(),
+            amount: balance,
+            balance: 0,
+            mint: Some(self.mint.key()),
+        });
+
+        harvest_withheld_fees(
+            &self.token_program.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault_ata.to_account_info(),
+        )?;
+        let close_accounts = CloseAccount {
+            account: self.vault_ata.to_account_info(),
+            destination: self.beneficiary.to_account_info(),
+            authority: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)?;
+
+        let mint_key = self.mint.key();
+        self.vault_state
+            .token_mints
+            .retain(|mint| *mint != mint_key);
+        Ok(())
+    }
+}
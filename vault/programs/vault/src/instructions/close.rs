@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::error::VaultError;
+use crate::events::VaultClosed;
+use crate::instructions::ensure_token_accounts_empty;
+use crate::state::{UserVaults, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Close<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+        close = user // Closes the account and sends lamports to user
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"vaults", vault_state.owner.as_ref()],
+        bump = user_vaults.bump
+    )]
+    pub user_vaults: Account<'info, UserVaults>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Close<'info> {
+    pub fn close(&mut self, token_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        require!(
+            self.vault_state.token_mints.is_empty(),
+            VaultError::TokenVaultsNotEmpty
+        );
+        ensure_token_accounts_empty(&self.vault.key(), token_accounts)?;
+        require!(self.vault_state.committed == 0, VaultError::StreamsActive);
+        require!(
+            self.vault_state.open_proposals == 0,
+            VaultError::ProposalsOpen
+        );
+        require!(self.vault_state.allowances == 0, VaultError::AllowancesOpen);
+        require!(
+            self.vault_state.stake_accounts == 0,
+            VaultError::StakeAccountsOpen
+        );
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        require!(self.vault_state.is_unlocked(now), VaultError::VaultLocked);
+        require!(
+            self.vault_state.locked_amount(now) == 0,
+            VaultError::FundsLocked
+        );
+        let balance = self.vault.lamports();
+        self.vault_state.consume_withdraw_limit(balance, now)?;
+        self.vault_state.consume_rate_limit(balance, &clock)?;
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user.to_account_info(),
+        };
+        // Store the Pubkey in a let binding
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(), // Use the stored Pubkey
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, balance)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(balance);
+        emit!(VaultClosed {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.user.key(),
+            amount: balance,
+        });
+
+        self.user_vaults.remove(self.vault_state.vault_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use anchor_lang::prelude::*;
+
+use crate::error::VaultError;
+use crate::state::{PendingRateLimit, RateLimit, TokenLimit, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Configure<'info> {
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+impl<'info> Configure<'info> {
+    pub fn extend_lock(&mut self, unlock_at: i64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(unlock_at > now, VaultError::InvalidUnlockTime);
+        if let Some(current) = self.vault_state.unlock_at {
+            require!(unlock_at >= current, VaultError::InvalidUnlockTime);
+        }
+
+        self.vault_state.unlock_at = Some(unlock_at);
+        self.vault_state.last_active = now;
+        Ok(())
+    }
+
+    /// Replaces the guardian set. Once guardians exist, `threshold` of them
+    /// must co-sign the change as remaining accounts.
+    pub fn set_guardians(
+        &mut self,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+        withdraw_limit: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.vault_state
+            .require_guardian_quorum(remaining_accounts)?;
+
+        require!(
+            guardians.len() <= VaultState::MAX_GUARDIANS,
+            VaultError::InvalidGuardians
+        );
+        require!(
+            threshold as usize <= guardians.len() && (threshold > 0 || guardians.is_empty()),
+            VaultError::InvalidGuardians
+        );
+        for (i, guardian) in guardians.iter().enumerate() {
+            require!(
+                !guardians[..i].contains(guardian),
+                VaultError::InvalidGuardians
+            );
+        }
+
+        self.vault_state.guardians = guardians;
+        self.vault_state.threshold = threshold;
+        self.vault_state.withdraw_limit = withdraw_limit;
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Sets how many base units of `mint` may leave a guarded vault per
+    /// `APPROVAL_WINDOW` without guardian approval; zero removes the limit.
+    /// Once guardians exist, `threshold` of them must co-sign the change.
+    pub fn set_token_limit(
+        &mut self,
+        mint: Pubkey,
+        withdraw_limit: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.vault_state
+            .require_guardian_quorum(remaining_accounts)?;
+
+        let token_limits = &mut self.vault_state.token_limits;
+        if withdraw_limit == 0 {
+            token_limits.retain(|limit| limit.mint != mint);
+        } else if let Some(limit) = token_limits.iter_mut().find(|limit| limit.mint == mint) {
+            limit.withdraw_limit = withdraw_limit;
+        } else {
+            require!(
+                token_limits.len() < VaultState::MAX_TOKEN_MINTS,
+                VaultError::TooManyTokenMints
+            );
+            token_limits.push(TokenLimit {
+                mint,
+                withdraw_limit,
+                window_start: 0,
+                withdrawn: 0,
+            });
+        }
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// A vault without a limit takes the new one immediately; changing or
+    /// removing an active limit only applies after `RATE_LIMIT_DELAY`.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) -> Result<()> {
+        if let Some(limit) = limit {
+            require!(limit.window > 0, VaultError::InvalidRateLimit);
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        self.vault_state.apply_pending_rate_limit(now);
+
+        let effective_at = if self.vault_state.rate_limit.is_some() {
+            now + VaultState::RATE_LIMIT_DELAY
+        } else {
+            now
+        };
+        self.vault_state.pending_rate_limit = Some(PendingRateLimit {
+            limit,
+            effective_at,
+        });
+        self.vault_state.apply_pending_rate_limit(now);
+        self.vault_state.last_active = now;
+        Ok(())
+    }
+
+    /// Names who can claim the vault after `inactivity_period` seconds without
+    /// an owner-signed instruction. Guarded vaults need guardian co-signers.
+    pub fn set_beneficiary(
+        &mut self,
+        beneficiary: Option<Pubkey>,
+        inactivity_period: i64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        self.vault_state
+            .require_guardian_quorum(remaining_accounts)?;
+        if beneficiary.is_some() {
+            // A claim skips the rate limit, so it must not be faster than a limit change
+            let min_period = if self.vault_state.rate_limit.is_some() {
+                VaultState::RATE_LIMIT_DELAY
+            } else {
+                1
+            };
+            require!(
+                inactivity_period >= min_period,
+                VaultError::InvalidInactivityPeriod
+            );
+        }
+
+        self.vault_state.beneficiary = beneficiary;
+        self.vault_state.inactivity_period = inactivity_period;
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Hands control of the vault to `new_authority`; the PDAs keep their address.
+    pub fn transfer_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        self.vault_state.authority = new_authority;
+        self.vault_state.pending_recovery = None;
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    pub fn set_recovery(&mut self, recovery_keys: Vec<Pubkey>, recovery_delay: i64) -> Result<()> {
+        require!(
+            recovery_keys.len() <= VaultState::MAX_RECOVERY_KEYS,
+            VaultError::InvalidRecovery
+        );
+        require!(
+            recovery_delay > 0 || recovery_keys.is_empty(),
+            VaultError::InvalidRecovery
+        );
+
+        self.vault_state.recovery_keys = recovery_keys;
+        self.vault_state.recovery_delay = recovery_delay;
+        self.vault_state.pending_recovery = None;
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    pub fn cancel_recovery(&mut self) -> Result<()> {
+        require!(
+            self.vault_state.pending_recovery.is_some(),
+            VaultError::NoPendingRecovery
+        );
+
+        self.vault_state.pending_recovery = None;
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+
+use crate::error::VaultError;
+use crate::events::VaultInitialized;
+use crate::state::{UserVaults, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"state", user.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump,
+        space = VaultState::INIT_SPACE
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"vaults", user.key().as_ref()],
+        bump,
+        space = UserVaults::INIT_SPACE
+    )]
+    pub user_vaults: Account<'info, UserVaults>,
+    #[account(
+        seeds = [b"vault", user.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Initialize<'info> {
+    pub fn initialize(
+        &mut self,
+        vault_id: u64,
+        unlock_at: Option<i64>,
+        bumps: &InitializeBumps,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        if let Some(unlock_at) = unlock_at {
+            require!(unlock_at > now, VaultError::InvalidUnlockTime);
+        }
+
+        require!(
+            self.user_vaults.vault_ids.len() < UserVaults::MAX_VAULTS,
+            VaultError::TooManyVaults
+        );
+        self.user_vaults.vault_ids.push(vault_id);
+        self.user_vaults.bump = bumps.user_vaults;
+
+        self.vault_state.owner = self.user.key();
+        self.vault_state.authority = self.user.key();
+        self.vault_state.vault_id = vault_id;
+        self.vault_state.vault_bump = bumps.vault;
+        self.vault_state.state_bump = bumps.vault_state;
+        self.vault_state.unlock_at = unlock_at;
+        self.vault_state.last_active = now;
+        self.vault_state.created_at = now;
+
+        emit!(VaultInitialized {
+            owner: self.vault_state.owner,
+            vault_id,
+            created_at: now,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+pub mod allowance;
+pub mod claim;
+pub mod close;
+pub mod configure;
+pub mod initialize;
+pub mod payment;
+pub mod proposal;
+pub mod recovery;
+pub mod spl_payment;
+pub mod stake;
+pub mod stream;
+
+pub use allowance::*;
+pub use claim::*;
+pub use close::*;
+pub use configure::*;
+pub use initialize::*;
+pub use payment::*;
+pub use proposal::*;
+pub use recovery::*;
+pub use spl_payment::*;
+pub use stake::*;
+pub use stream::*;
@@ -0,0 +1,108 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::error::VaultError;
+use crate::events::{Deposited, Withdrawn};
+use crate::state::{Lock, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Payment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Payment<'info> {
+    pub fn deposit(&mut self, amount: u64) -> Result<()> {
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.user.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_deposited = self.vault_state.total_deposited.saturating_add(amount);
+        emit!(Deposited {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            amount,
+            balance: self.vault.lamports(),
+            mint: None,
+        });
+        Ok(())
+    }
+
+    pub fn deposit_locked(&mut self, amount: u64, unlock_at: i64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(unlock_at > now, VaultError::InvalidUnlockTime);
+
+        self.vault_state.prune_locks(now);
+        require!(
+            self.vault_state.locks.len() < VaultState::MAX_LOCKS,
+            VaultError::TooManyLocks
+        );
+        self.vault_state.locks.push(Lock { amount, unlock_at });
+
+        self.deposit(amount)
+    }
+
+    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        self.vault_state.ensure_withdrawable(
+            amount,
+            self.vault.lamports(),
+            clock.unix_timestamp,
+        )?;
+        self.vault_state
+            .consume_withdraw_limit(amount, clock.unix_timestamp)?;
+        self.vault_state.consume_rate_limit(amount, &clock)?;
+        self.vault_state.prune_locks(clock.unix_timestamp);
+        self.vault_state.last_active = clock.unix_timestamp;
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user.to_account_info(),
+        };
+        // Store the Pubkey in a let binding
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(), // Use the stored Pubkey
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(amount);
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.user.key(),
+            amount,
+            balance: self.vault.lamports(),
+            mint: None,
+        });
+        Ok(())
+    }
+}
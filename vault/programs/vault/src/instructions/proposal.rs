@@ -0,0 +1,202 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::error::VaultError;
+use crate::events::Withdrawn;
+use crate::state::{VaultState, WithdrawalProposal};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct ProposeWithdrawal<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            b"proposal",
+            vault_state.key().as_ref(),
+            vault_state.proposal_count.to_le_bytes().as_ref()
+        ],
+        space = WithdrawalProposal::INIT_SPACE,
+        bump
+    )]
+    pub proposal: Account<'info, WithdrawalProposal>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveWithdrawal<'info> {
+    pub guardian: Signer<'info>,
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        has_one = vault_state,
+    )]
+    pub proposal: Account<'info, WithdrawalProposal>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct ResolveWithdrawal<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        has_one = vault_state,
+        close = user
+    )]
+    pub proposal: Account<'info, WithdrawalProposal>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct CancelWithdrawal<'info> {
+    /// The authority, or the beneficiary of a claimable vault
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        has_one = vault_state,
+        close = user
+    )]
+    pub proposal: Account<'info, WithdrawalProposal>,
+}
+
+impl<'info> ProposeWithdrawal<'info> {
+    pub fn propose_withdrawal(
+        &mut self,
+        amount: u64,
+        bumps: &ProposeWithdrawalBumps,
+    ) -> Result<()> {
+        require!(self.vault_state.guarded(), VaultError::InvalidGuardians);
+
+        self.proposal.set_inner(WithdrawalProposal {
+            vault_state: self.vault_state.key(),
+            amount,
+            approvals: Vec::new(),
+            bump: bumps.proposal,
+        });
+        self.vault_state.proposal_count += 1;
+        self.vault_state.open_proposals += 1;
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+}
+
+impl<'info> ApproveWithdrawal<'info> {
+    pub fn approve_withdrawal(&mut self) -> Result<()> {
+        let guardian = self.guardian.key();
+        require!(
+            self.vault_state.guardians.contains(&guardian),
+            VaultError::NotGuardian
+        );
+        require!(
+            !self.proposal.approvals.contains(&guardian),
+            VaultError::AlreadyApproved
+        );
+
+        // Drop approvals from guardians removed since, which no longer count
+        // and would otherwise overflow the space sized for `MAX_GUARDIANS`
+        let guardians = &self.vault_state.guardians;
+        self.proposal
+            .approvals
+            .retain(|approver| guardians.contains(approver));
+        self.proposal.approvals.push(guardian);
+        Ok(())
+    }
+}
+
+impl<'info> ResolveWithdrawal<'info> {
+    pub fn execute_withdrawal(&mut self) -> Result<()> {
+        // Approvals from guardians removed since the proposal was opened no longer count
+        let approvals = self
+            .vault_state
+            .guardian_approvals(self.proposal.approvals.iter());
+        require!(
+            approvals >= self.vault_state.threshold as usize,
+            VaultError::ThresholdNotMet
+        );
+
+        let amount = self.proposal.amount;
+        let clock = Clock::get()?;
+        self.vault_state.ensure_withdrawable(
+            amount,
+            self.vault.lamports(),
+            clock.unix_timestamp,
+        )?;
+        self.vault_state.consume_rate_limit(amount, &clock)?;
+        self.vault_state.prune_locks(clock.unix_timestamp);
+        self.vault_state.open_proposals = self.vault_state.open_proposals.saturating_sub(1);
+        self.vault_state.last_active = clock.unix_timestamp;
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.user.to_account_info(),
+        };
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(amount);
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.user.key(),
+            amount,
+            balance: self.vault.lamports(),
+            mint: None,
+        });
+        Ok(())
+    }
+}
+
+impl<'info> CancelWithdrawal<'info> {
+    /// Nothing moves, so cancelling only refunds the proposal rent.
+    pub fn cancel_withdrawal(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        self.vault_state
+            .ensure_can_wind_down(&self.user.key(), now)?;
+        self.vault_state.open_proposals = self.vault_state.open_proposals.saturating_sub(1);
+        if self.user.key() == self.vault_state.authority {
+            self.vault_state.last_active = now;
+        }
+        Ok(())
+    }
+}
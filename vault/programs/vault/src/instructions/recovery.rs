@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+
+use crate::error::VaultError;
+use crate::state::{PendingRecovery, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct Recover<'info> {
+    pub recovery_key: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.recovery_keys.contains(&recovery_key.key()) @ VaultError::NotRecoveryKey,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+impl<'info> Recover<'info> {
+    /// Starts the timelock; the current authority can cancel it until it
+    /// elapses. A pending recovery cannot be replaced, so another recovery
+    /// key cannot restart the clock or swap in its own authority.
+    pub fn initiate_recovery(&mut self, new_authority: Pubkey) -> Result<()> {
+        require!(
+            self.vault_state.pending_recovery.is_none(),
+            VaultError::RecoveryPending
+        );
+        self.vault_state.pending_recovery = Some(PendingRecovery {
+            new_authority,
+            initiated_at: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    pub fn complete_recovery(&mut self) -> Result<()> {
+        let pending = self
+            .vault_state
+            .pending_recovery
+            .ok_or(VaultError::NoPendingRecovery)?;
+        require!(
+            Clock::get()?.unix_timestamp
+                >= pending
+                    .initiated_at
+                    .saturating_add(self.vault_state.recovery_delay),
+            VaultError::RecoveryNotReady
+        );
+
+        self.vault_state.authority = pending.new_authority;
+        self.vault_state.pending_recovery = None;
+        Ok(())
+    }
+}
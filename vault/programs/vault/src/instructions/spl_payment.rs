@@ -0,0 +1,250 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+use crate::error::VaultError;
+use crate::events::{Deposited, Withdrawn};
+use crate::state::VaultState;
+
+pub use token_fees::harvest_withheld_fees;
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct SplPayment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program
+    )]
+    pub vault_ata: InterfaceAccount<'info, TokenAccount>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SplPayment<'info> {
+    pub fn deposit_spl(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, VaultError::ZeroAmount);
+        self.vault_state.last_active = Clock::get()?.unix_timestamp;
+        let mint_key = self.mint.key();
+        if !self.vault_state.token_mints.contains(&mint_key) {
+            require!(
+                self.vault_state.token_mints.len() < VaultState::MAX_TOKEN_MINTS,
+                VaultError::TooManyTokenMints
+            );
+            self.vault_state.token_mints.push(mint_key);
+        }
+
+        let transfer_accounts = TransferChecked {
+            from: self.user_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.vault_ata.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.vault_ata.reload()?;
+        emit!(Deposited {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            amount,
+            balance: self.vault_ata.amount,
+            mint: Some(mint_key),
+        });
+        Ok(())
+    }
+
+    /// Withdrawals within the mint's entry in `token_limits` only need the
+    /// authority, as lamports within `withdraw_limit` do. Proposals only pay
+    /// out lamports, so larger token withdrawals from a guarded vault need
+    /// its guardians to co-sign instead.
+    pub fn withdraw_spl(
+        &mut self,
+        amount: u64,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.vault_state.is_unlocked(now), VaultError::VaultLocked);
+        let mint_key = self.mint.key();
+        let within_limit = self.vault_state.consume_token_limit(&mint_key, amount, now);
+        if self.vault_state.guarded() && !within_limit {
+            self.vault_state
+                .require_guardian_quorum(remaining_accounts)?;
+        }
+        self.vault_state.last_active = now;
+
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ]];
+
+        let transfer_accounts = TransferChecked {
+            from: self.vault_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.user_ata.to_account_info(),
+            authority: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        );
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.vault_ata.reload()?;
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.user.key(),
+            amount,
+            balance: self.vault_ata.amount,
+            mint: Some(mint_key),
+        });
+        if self.vault_ata.amount > 0 {
+            return Ok(());
+        }
+
+        // Close the emptied token vault so its rent goes back to the user
+        harvest_withheld_fees(
+            &self.token_program.to_account_info(),
+            &self.mint.to_account_info(),
+            &self.vault_ata.to_account_info(),
+        )?;
+        let close_accounts = CloseAccount {
+            account: self.vault_ata.to_account_info(),
+            destination: self.user.to_account_info(),
+            authority: self.vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        );
+        close_account(cpi_ctx)?;
+
+        self.vault_state
+            .token_mints
+            .retain(|mint| *mint != mint_key);
+        Ok(())
+    }
+}
+
+/// Requires each of the vault's token accounts in `token_accounts` to be
+/// empty. `token_mints` only knows about `deposit_spl`, so this also catches
+/// tokens sent straight to a vault token account.
+pub fn ensure_token_accounts_empty<'info>(
+    vault: &Pubkey,
+    token_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    for account in token_accounts {
+        let token_account = InterfaceAccount::<TokenAccount>::try_from(account)?;
+        require_keys_eq!(
+            token_account.owner,
+            *vault,
+            VaultError::NotVaultTokenAccount
+        );
+        require!(token_account.amount == 0, VaultError::TokenVaultsNotEmpty);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_spl::token::spl_token::{
+        self,
+        solana_program::program_pack::Pack,
+        state::{Account, AccountState},
+    };
+
+    use super::*;
+
+    fn token_account_data(owner: Pubkey, amount: u64) -> Vec<u8> {
+        let mut data = vec![0; Account::LEN];
+        Account {
+            mint: Pubkey::new_unique(),
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        }
+        .pack_into_slice(&mut data);
+        data
+    }
+
+    fn check(vault: Pubkey, owner: Pubkey, amount: u64) -> Result<()> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 1;
+        let mut data = token_account_data(owner, amount);
+        let account = AccountInfo::new(
+            &key,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &spl_token::ID,
+            false,
+            0,
+        );
+        ensure_token_accounts_empty(&vault, std::slice::from_ref(&account))
+    }
+
+    #[test]
+    fn token_accounts_must_be_empty() {
+        let vault = Pubkey::new_unique();
+        check(vault, vault, 0).unwrap();
+        assert_eq!(
+            check(vault, vault, 1).unwrap_err(),
+            VaultError::TokenVaultsNotEmpty.into()
+        );
+    }
+
+    #[test]
+    fn token_accounts_must_belong_to_the_vault() {
+        assert_eq!(
+            check(Pubkey::new_unique(), Pubkey::new_unique(), 0).unwrap_err(),
+            VaultError::NotVaultTokenAccount.into()
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    program::{invoke, invoke_signed},
+    stake::{
+        self,
+        instruction::{delegate_stake, initialize},
+        state::{Authorized, Lockup, StakeStateV2},
+    },
+    sysvar::stake_history,
+};
+use anchor_lang::system_program::{allocate, assign, transfer, Allocate, Assign, Transfer};
+use anchor_spl::stake::{deactivate_stake, withdraw, DeactivateStake, Stake, Withdraw};
+
+use crate::error::VaultError;
+use crate::state::VaultState;
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct DelegateStake<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    /// CHECK: created and initialized here as a stake account owned by the stake program
+    #[account(
+        mut,
+        seeds = [
+            b"stake",
+            vault_state.key().as_ref(),
+            vault_state.stake_count.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+    /// CHECK: validated by the stake program when delegating
+    pub validator_vote: UncheckedAccount<'info>,
+    /// CHECK: stake config account, still required by the delegate instruction
+    #[account(address = stake::config::ID)]
+    pub stake_config: UncheckedAccount<'info>,
+    /// CHECK: stake history sysvar
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, stake_id: u64)]
+pub struct Unstake<'info> {
+    /// The authority, or the beneficiary of a claimable vault
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    /// CHECK: stake account derived from the vault; the stake program checks its state
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref(), stake_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, Stake>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64, stake_id: u64)]
+pub struct WithdrawStake<'info> {
+    /// The authority, or the beneficiary of a claimable vault
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    /// CHECK: stake account derived from the vault; the stake program checks its state
+    #[account(
+        mut,
+        seeds = [b"stake", vault_state.key().as_ref(), stake_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub stake_account: UncheckedAccount<'info>,
+    /// CHECK: stake history sysvar
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, Stake>,
+}
+
+impl<'info> DelegateStake<'info> {
+    /// Moves `amount` lamports, including the stake account's rent reserve,
+    /// into a new stake account whose staker and withdrawer is the vault.
+    /// Staked lamports leave the vault until withdrawn, so they pass the same
+    /// locks and limits as a withdrawal and cannot touch what open streams
+    /// are owed or the rent behind them.
+    pub fn delegate_stake(&mut self, amount: u64, bumps: &DelegateStakeBumps) -> Result<()> {
+        let clock = Clock::get()?;
+        self.vault_state.ensure_committable(
+            amount,
+            self.vault.lamports(),
+            clock.unix_timestamp,
+        )?;
+        self.vault_state
+            .consume_withdraw_limit(amount, clock.unix_timestamp)?;
+        self.vault_state.consume_rate_limit(amount, &clock)?;
+        self.vault_state.prune_locks(clock.unix_timestamp);
+
+        let vault_state_key = self.vault_state.key();
+        let stake_id = self.vault_state.stake_count.to_le_bytes();
+        let stake_seeds = &[
+            b"stake",
+            vault_state_key.as_ref(),
+            stake_id.as_ref(),
+            &[bumps.stake_account],
+        ];
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let vault_seeds = &[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let vault_signer = &[&vault_seeds[..]];
+        let stake_signer = &[&stake_seeds[..]];
+
+        // Funded, allocated and assigned separately rather than with
+        // `create_account`, which fails if anyone has already sent lamports
+        // to this predictable address
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.stake_account.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            vault_signer,
+        );
+        transfer(cpi_ctx, amount)?;
+
+        let cpi_accounts = Allocate {
+            account_to_allocate: self.stake_account.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            stake_signer,
+        );
+        allocate(cpi_ctx, StakeStateV2::size_of() as u64)?;
+
+        let cpi_accounts = Assign {
+            account_to_assign: self.stake_account.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.system_program.to_account_info(),
+            cpi_accounts,
+            stake_signer,
+        );
+        assign(cpi_ctx, &self.stake_program.key())?;
+
+        let vault_key = self.vault.key();
+        let authorized = Authorized {
+            staker: vault_key,
+            withdrawer: vault_key,
+        };
+        invoke(
+            &initialize(&self.stake_account.key(), &authorized, &Lockup::default()),
+            &[
+                self.stake_account.to_account_info(),
+                self.rent.to_account_info(),
+            ],
+        )?;
+
+        invoke_signed(
+            &delegate_stake(
+                &self.stake_account.key(),
+                &vault_key,
+                &self.validator_vote.key(),
+            ),
+            &[
+                self.stake_account.to_account_info(),
+                self.validator_vote.to_account_info(),
+                self.clock.to_account_info(),
+                self.stake_history.to_account_info(),
+                self.stake_config.to_account_info(),
+                self.vault.to_account_info(),
+            ],
+            &[&vault_seeds[..]],
+        )?;
+
+        self.vault_state.stake_count += 1;
+        self.vault_state.stake_accounts += 1;
+        self.vault_state.last_active = self.clock.unix_timestamp;
+        Ok(())
+    }
+}
+
+impl<'info> Unstake<'info> {
+    pub fn unstake(&mut self) -> Result<()> {
+        let now = self.clock.unix_timestamp;
+        self.vault_state
+            .ensure_can_wind_down(&self.user.key(), now)?;
+
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ]];
+
+        let cpi_accounts = DeactivateStake {
+            stake: self.stake_account.to_account_info(),
+            staker: self.vault.to_account_info(),
+            clock: self.clock.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.stake_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        deactivate_stake(cpi_ctx)?;
+
+        if self.user.key() == self.vault_state.authority {
+            self.vault_state.last_active = now;
+        }
+        Ok(())
+    }
+}
+
+impl<'info> WithdrawStake<'info> {
+    /// Returns a fully deactivated stake account, rewards included, to the vault.
+    pub fn withdraw_stake(&mut self) -> Result<()> {
+        let now = self.clock.unix_timestamp;
+        self.vault_state
+            .ensure_can_wind_down(&self.user.key(), now)?;
+
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ]];
+
+        let cpi_accounts = Withdraw {
+            stake: self.stake_account.to_account_info(),
+            withdrawer: self.vault.to_account_info(),
+            to: self.vault.to_account_info(),
+            clock: self.clock.to_account_info(),
+            stake_history: self.stake_history.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            self.stake_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        withdraw(cpi_ctx, self.stake_account.lamports(), None)?;
+
+        self.vault_state.stake_accounts -= 1;
+        if self.user.key() == self.vault_state.authority {
+            self.vault_state.last_active = now;
+        }
+        Ok(())
+    }
+}
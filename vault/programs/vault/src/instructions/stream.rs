@@ -0,0 +1,233 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+use crate::error::VaultError;
+use crate::events::Withdrawn;
+use crate::state::{Stream, VaultState};
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct CreateStream<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::Unauthorized,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            b"stream",
+            vault_state.key().as_ref(),
+            vault_state.stream_count.to_le_bytes().as_ref()
+        ],
+        space = Stream::INIT_SPACE,
+        bump
+    )]
+    pub stream: Account<'info, Stream>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct WithdrawStream<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    pub owner: SystemAccount<'info>,
+    /// Receives the stream's rent once it is fully paid out, as the owner's
+    /// key may no longer control the vault
+    #[account(
+        mut,
+        address = vault_state.authority @ VaultError::Unauthorized
+    )]
+    pub authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", owner.key().as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        has_one = vault_state,
+        has_one = recipient,
+    )]
+    pub stream: Account<'info, Stream>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault_id: u64)]
+pub struct CancelStream<'info> {
+    /// The authority, or the beneficiary of a claimable vault
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"state", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+    #[account(
+        mut,
+        seeds = [b"vault", vault_state.owner.as_ref(), vault_id.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        has_one = vault_state,
+        has_one = recipient,
+        close = user
+    )]
+    pub stream: Account<'info, Stream>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateStream<'info> {
+    /// Reserves the whole stream up front, so it counts against guardian,
+    /// lock and rate limits once at creation rather than on every payout.
+    pub fn create_stream(
+        &mut self,
+        recipient: Pubkey,
+        rate_per_second: u64,
+        start: i64,
+        end: i64,
+        bumps: &CreateStreamBumps,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(
+            rate_per_second > 0 && end > start && end > clock.unix_timestamp,
+            VaultError::InvalidStream
+        );
+
+        self.stream.set_inner(Stream {
+            owner: self.vault_state.owner,
+            vault_state: self.vault_state.key(),
+            recipient,
+            id: self.vault_state.stream_count,
+            rate_per_second,
+            start,
+            end,
+            withdrawn: 0,
+            bump: bumps.stream,
+        });
+        let total = self.stream.total().ok_or(VaultError::InvalidStream)?;
+
+        self.vault_state
+            .ensure_committable(total, self.vault.lamports(), clock.unix_timestamp)?;
+        self.vault_state
+            .consume_withdraw_limit(total, clock.unix_timestamp)?;
+        self.vault_state.consume_rate_limit(total, &clock)?;
+
+        self.vault_state.committed += total;
+        self.vault_state.stream_count += 1;
+        self.vault_state.last_active = clock.unix_timestamp;
+        Ok(())
+    }
+}
+
+impl<'info> WithdrawStream<'info> {
+    pub fn withdraw_stream(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let amount = self.stream.withdrawable(now);
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.recipient.to_account_info(),
+        };
+        let owner_key = self.owner.key();
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(amount);
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.recipient.key(),
+            amount,
+            balance: self.vault.lamports(),
+            mint: None,
+        });
+
+        self.stream.withdrawn += amount;
+        self.vault_state.committed -= amount;
+
+        if now >= self.stream.end {
+            self.stream.close(self.authority.to_account_info())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'info> CancelStream<'info> {
+    /// Pays the recipient what has vested so far; the unvested remainder
+    /// stays in the vault for the owner, or for a beneficiary settling
+    /// streams before a claim.
+    pub fn cancel_stream(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        self.vault_state
+            .ensure_can_wind_down(&self.user.key(), now)?;
+        let amount = self.stream.withdrawable(now);
+        let total = self.stream.total().ok_or(VaultError::InvalidStream)?;
+
+        let cpi_program = self.system_program.to_account_info();
+        let cpi_accounts = Transfer {
+            from: self.vault.to_account_info(),
+            to: self.recipient.to_account_info(),
+        };
+        let owner_key = self.vault_state.owner;
+        let vault_id = self.vault_state.vault_id.to_le_bytes();
+        let seeds = &[
+            b"vault",
+            owner_key.as_ref(),
+            vault_id.as_ref(),
+            &[self.vault_state.vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        transfer(cpi_ctx, amount)?;
+
+        self.vault_state.total_withdrawn = self.vault_state.total_withdrawn.saturating_add(amount);
+        emit!(Withdrawn {
+            owner: self.vault_state.owner,
+            vault_id: self.vault_state.vault_id,
+            recipient: self.recipient.key(),
+            amount,
+            balance: self.vault.lamports(),
+            mint: None,
+        });
+
+        self.vault_state.committed -= total - self.stream.withdrawn;
+        if self.user.key() == self.vault_state.authority {
+            self.vault_state.last_active = now;
+        }
+        Ok(())
+    }
+}
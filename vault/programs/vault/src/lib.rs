@@ -1,153 +1,227 @@
 use anchor_lang::prelude::*;
-use anchor_lang::system_program::{transfer, Transfer};
 
 declare_id!("9ApqhVdUbjEuG6RsMYi7bz8BK87FG9eLHBLniFgGLB9v");
 
+pub mod error;
+pub mod events;
+pub mod instructions;
+pub mod state;
+
+pub use events::*;
+pub use instructions::*;
+pub use state::*;
+
 #[program]
+// `vault_id` and `stake_id` are usually only consumed by the account seed constraints
+#[allow(unused_variables)]
 pub mod vault {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        ctx.accounts.initialize(&ctx.bumps)
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        vault_id: u64,
+        unlock_at: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts.initialize(vault_id, unlock_at, &ctx.bumps)
     }
 
-    pub fn deposit(ctx: Context<Payment>, amount: u64) -> Result<()> {
+    pub fn deposit(ctx: Context<Payment>, vault_id: u64, amount: u64) -> Result<()> {
         ctx.accounts.deposit(amount)
     }
 
-    pub fn withdraw(ctx: Context<Payment>, amount: u64) -> Result<()> {
+    pub fn deposit_locked(
+        ctx: Context<Payment>,
+        vault_id: u64,
+        amount: u64,
+        unlock_at: i64,
+    ) -> Result<()> {
+        ctx.accounts.deposit_locked(amount, unlock_at)
+    }
+
+    pub fn withdraw(ctx: Context<Payment>, vault_id: u64, amount: u64) -> Result<()> {
         ctx.accounts.withdraw(amount)
     }
 
-    pub fn close(ctx: Context<Close>) -> Result<()> {
-        ctx.accounts.close()
+    pub fn deposit_spl(ctx: Context<SplPayment>, vault_id: u64, amount: u64) -> Result<()> {
+        ctx.accounts.deposit_spl(amount)
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(
-        init,
-        payer = user,
-        seeds = [b"state", user.key().as_ref()],
-        bump,
-        space = VaultState::INIT_SPACE
-    )]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        seeds = [b"vault", user.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
-    pub system_program: Program<'info, System>,
-}
+    /// Remaining accounts: guardian co-signers, if a guarded vault withdraws
+    /// more than the mint's limit in `token_limits`
+    pub fn withdraw_spl<'info>(
+        ctx: Context<'_, '_, '_, 'info, SplPayment<'info>>,
+        vault_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.withdraw_spl(amount, ctx.remaining_accounts)
+    }
 
-#[derive(Accounts)]
-pub struct Payment<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"state", user.key().as_ref()],
-        bump = vault_state.state_bump,
-    )]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        mut,
-        seeds = [b"vault", user.key().as_ref()],
-        bump = vault_state.vault_bump
-    )]
-    pub vault: SystemAccount<'info>,
-    pub system_program: Program<'info, System>,
-}
+    pub fn extend_lock(ctx: Context<Configure>, vault_id: u64, unlock_at: i64) -> Result<()> {
+        ctx.accounts.extend_lock(unlock_at)
+    }
 
-#[derive(Accounts)]
-pub struct Close<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
-    #[account(
-        mut,
-        seeds = [b"state", user.key().as_ref()],
-        bump = vault_state.state_bump,
-        close = user // Closes the account and sends lamports to user
-    )]
-    pub vault_state: Account<'info, VaultState>,
-    #[account(
-        mut,
-        seeds = [b"vault", user.key().as_ref()],
-        bump = vault_state.vault_bump
-    )]
-    pub vault: SystemAccount<'info>,
-    pub system_program: Program<'info, System>,
-}
+    pub fn set_guardians<'info>(
+        ctx: Context<'_, '_, '_, 'info, Configure<'info>>,
+        vault_id: u64,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+        withdraw_limit: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_guardians(guardians, threshold, withdraw_limit, ctx.remaining_accounts)
+    }
 
-impl<'info> Initialize<'info> {
-    pub fn initialize(&mut self, bumps: &InitializeBumps) -> Result<()> {
-        self.vault_state.vault_bump = bumps.vault;
-        self.vault_state.state_bump = bumps.vault_state;
-        Ok(())
+    /// Remaining accounts: guardian co-signers, if the vault is guarded
+    pub fn set_token_limit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Configure<'info>>,
+        vault_id: u64,
+        mint: Pubkey,
+        withdraw_limit: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_token_limit(mint, withdraw_limit, ctx.remaining_accounts)
     }
-}
 
-impl<'info> Payment<'info> {
-    pub fn deposit(&mut self, amount: u64) -> Result<()> {
-        let cpi_program = self.system_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: self.user.to_account_info(),
-            to: self.vault.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        transfer(cpi_ctx, amount)
-    }
-
-    pub fn withdraw(&mut self, amount: u64) -> Result<()> {
-        let cpi_program = self.system_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: self.vault.to_account_info(),
-            to: self.user.to_account_info(),
-        };
-        // Store the Pubkey in a let binding
-        let user_key = self.user.key();
-        let seeds = &[
-            b"vault",
-            user_key.as_ref(), // Use the stored Pubkey
-            &[self.vault_state.vault_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        transfer(cpi_ctx, amount)
+    pub fn set_rate_limit(
+        ctx: Context<Configure>,
+        vault_id: u64,
+        limit: Option<RateLimit>,
+    ) -> Result<()> {
+        ctx.accounts.set_rate_limit(limit)
     }
-}
 
-impl<'info> Close<'info> {
-    pub fn close(&mut self) -> Result<()> {
-        let cpi_program = self.system_program.to_account_info();
-        let cpi_accounts = Transfer {
-            from: self.vault.to_account_info(),
-            to: self.user.to_account_info(),
-        };
-        // Store the Pubkey in a let binding
-        let user_key = self.user.key();
-        let seeds = &[
-            b"vault",
-            user_key.as_ref(), // Use the stored Pubkey
-            &[self.vault_state.vault_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        transfer(cpi_ctx, self.vault.lamports())?;
-        Ok(())
+    pub fn set_beneficiary<'info>(
+        ctx: Context<'_, '_, '_, 'info, Configure<'info>>,
+        vault_id: u64,
+        beneficiary: Option<Pubkey>,
+        inactivity_period: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_beneficiary(beneficiary, inactivity_period, ctx.remaining_accounts)
     }
-}
 
-#[account]
-pub struct VaultState {
-    pub vault_bump: u8,
-    pub state_bump: u8,
-}
+    pub fn transfer_authority(
+        ctx: Context<Configure>,
+        vault_id: u64,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.transfer_authority(new_authority)
+    }
+
+    pub fn set_recovery(
+        ctx: Context<Configure>,
+        vault_id: u64,
+        recovery_keys: Vec<Pubkey>,
+        recovery_delay: i64,
+    ) -> Result<()> {
+        ctx.accounts.set_recovery(recovery_keys, recovery_delay)
+    }
+
+    pub fn cancel_recovery(ctx: Context<Configure>, vault_id: u64) -> Result<()> {
+        ctx.accounts.cancel_recovery()
+    }
+
+    pub fn initiate_recovery(
+        ctx: Context<Recover>,
+        vault_id: u64,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.initiate_recovery(new_authority)
+    }
+
+    pub fn complete_recovery(ctx: Context<Recover>, vault_id: u64) -> Result<()> {
+        ctx.accounts.complete_recovery()
+    }
+
+    pub fn propose_withdrawal(
+        ctx: Context<ProposeWithdrawal>,
+        vault_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.propose_withdrawal(amount, &ctx.bumps)
+    }
+
+    pub fn approve_withdrawal(ctx: Context<ApproveWithdrawal>) -> Result<()> {
+        ctx.accounts.approve_withdrawal()
+    }
 
-impl Space for VaultState {
-    const INIT_SPACE: usize = 8 + 1 + 1;
+    pub fn execute_withdrawal(ctx: Context<ResolveWithdrawal>, vault_id: u64) -> Result<()> {
+        ctx.accounts.execute_withdrawal()
+    }
+
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>, vault_id: u64) -> Result<()> {
+        ctx.accounts.cancel_withdrawal()
+    }
+
+    pub fn approve_allowance(
+        ctx: Context<ApproveAllowance>,
+        vault_id: u64,
+        delegate: Pubkey,
+        max_amount: u64,
+        expires_at: Option<i64>,
+        period: Option<SpendPeriod>,
+    ) -> Result<()> {
+        ctx.accounts
+            .approve_allowance(delegate, max_amount, expires_at, period, &ctx.bumps)
+    }
+
+    pub fn revoke_allowance(ctx: Context<RevokeAllowance>, vault_id: u64) -> Result<()> {
+        ctx.accounts.revoke_allowance()
+    }
+
+    pub fn spend(ctx: Context<Spend>, vault_id: u64, amount: u64) -> Result<()> {
+        ctx.accounts.spend(amount)
+    }
+
+    pub fn create_stream(
+        ctx: Context<CreateStream>,
+        vault_id: u64,
+        recipient: Pubkey,
+        rate_per_second: u64,
+        start: i64,
+        end: i64,
+    ) -> Result<()> {
+        ctx.accounts
+            .create_stream(recipient, rate_per_second, start, end, &ctx.bumps)
+    }
+
+    pub fn withdraw_stream(ctx: Context<WithdrawStream>, vault_id: u64) -> Result<()> {
+        ctx.accounts.withdraw_stream()
+    }
+
+    pub fn cancel_stream(ctx: Context<CancelStream>, vault_id: u64) -> Result<()> {
+        ctx.accounts.cancel_stream()
+    }
+
+    pub fn delegate_stake(ctx: Context<DelegateStake>, vault_id: u64, amount: u64) -> Result<()> {
+        ctx.accounts.delegate_stake(amount, &ctx.bumps)
+    }
+
+    pub fn unstake(ctx: Context<Unstake>, vault_id: u64, stake_id: u64) -> Result<()> {
+        ctx.accounts.unstake()
+    }
+
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>, vault_id: u64, stake_id: u64) -> Result<()> {
+        ctx.accounts.withdraw_stake()
+    }
+
+    /// Remaining accounts: the vault's token accounts, which must all be empty
+    pub fn claim<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Claim<'info>>,
+        vault_id: u64,
+    ) -> Result<()> {
+        ctx.accounts.claim(ctx.remaining_accounts)
+    }
+
+    pub fn claim_spl(ctx: Context<ClaimSpl>, vault_id: u64) -> Result<()> {
+        ctx.accounts.claim_spl()
+    }
+
+    /// Remaining accounts: the vault's token accounts, which must all be empty
+    pub fn close<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Close<'info>>,
+        vault_id: u64,
+    ) -> Result<()> {
+        ctx.accounts.close(ctx.remaining_accounts)
+    }
 }
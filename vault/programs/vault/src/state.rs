@@ -0,0 +1,770 @@
+use anchor_lang::prelude::*;
+
+use crate::error::VaultError;
+
+#[account]
+pub struct VaultState {
+    /// Key the vault PDAs are derived from; never changes.
+    pub owner: Pubkey,
+    /// Key allowed to sign for the vault, rotated by `transfer_authority` or recovery.
+    pub authority: Pubkey,
+    pub vault_id: u64,
+    pub vault_bump: u8,
+    pub state_bump: u8,
+    /// Mints deposited through `deposit_spl` whose vault token account still
+    /// holds a balance.
+    pub token_mints: Vec<Pubkey>,
+    /// Nothing can leave the vault before this unix timestamp.
+    pub unlock_at: Option<i64>,
+    /// Lamports locked by individual deposits, pruned once they unlock.
+    pub locks: Vec<Lock>,
+    /// Keys whose approval is needed for withdrawals above `withdraw_limit`
+    /// lamports, or a mint's entry in `token_limits`, per `APPROVAL_WINDOW`.
+    pub guardians: Vec<Pubkey>,
+    pub threshold: u8,
+    pub withdraw_limit: u64,
+    /// Lamports paid out without guardian approval since `approval_window_start`.
+    pub approval_window_start: i64,
+    pub approval_window_withdrawn: u64,
+    /// Per-mint counterparts of `withdraw_limit`. Guarded vaults need
+    /// guardians for every withdrawal of a mint without an entry.
+    pub token_limits: Vec<TokenLimit>,
+    pub proposal_count: u64,
+    /// Withdrawal proposals that have been neither executed nor cancelled.
+    pub open_proposals: u32,
+    /// Cap on lamports leaving the vault per window. Token withdrawals are
+    /// not counted; to cap those, add guardians and a `token_limits` entry
+    /// per mint.
+    pub rate_limit: Option<RateLimit>,
+    pub pending_rate_limit: Option<PendingRateLimit>,
+    pub window_start: u64,
+    pub window_withdrawn: u64,
+    /// Receives the vault once the owner has been inactive for `inactivity_period` seconds.
+    pub beneficiary: Option<Pubkey>,
+    pub inactivity_period: i64,
+    pub last_active: i64,
+    pub stream_count: u64,
+    /// Lamports still owed to open streams.
+    pub committed: u64,
+    pub stake_count: u64,
+    /// Stake accounts created by the vault that have not been withdrawn yet.
+    pub stake_accounts: u32,
+    /// Allowance accounts that have not been revoked yet.
+    pub allowances: u32,
+    /// Keys that may rotate the authority after `recovery_delay` seconds.
+    pub recovery_keys: Vec<Pubkey>,
+    pub recovery_delay: i64,
+    pub pending_recovery: Option<PendingRecovery>,
+    pub total_deposited: u64,
+    /// Lamports paid out of the vault, excluding stake delegations.
+    pub total_withdrawn: u64,
+    pub created_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Lock {
+    pub amount: u64,
+    pub unlock_at: i64,
+}
+
+/// Base units of `mint` a guarded vault may pay out per `APPROVAL_WINDOW`
+/// without guardian approval.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TokenLimit {
+    pub mint: Pubkey,
+    pub withdraw_limit: u64,
+    pub window_start: i64,
+    pub withdrawn: u64,
+}
+
+impl TokenLimit {
+    pub const SIZE: usize = 32 + 8 + 8 + 8;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum WindowUnit {
+    Slots,
+    Seconds,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct RateLimit {
+    pub max_amount: u64,
+    pub window: u64,
+    pub unit: WindowUnit,
+}
+
+impl RateLimit {
+    pub const SIZE: usize = 8 + 8 + 1;
+
+    fn now(&self, clock: &Clock) -> u64 {
+        match self.unit {
+            WindowUnit::Slots => clock.slot,
+            WindowUnit::Seconds => clock.unix_timestamp.max(0) as u64,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PendingRateLimit {
+    pub limit: Option<RateLimit>,
+    pub effective_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PendingRecovery {
+    pub new_authority: Pubkey,
+    pub initiated_at: i64,
+}
+
+impl Space for VaultState {
+    const INIT_SPACE: usize = 8
+        + 32
+        + 32
+        + 8
+        + 1
+        + 1
+        + (4 + VaultState::MAX_TOKEN_MINTS * 32)
+        + (1 + 8)
+        + (4 + VaultState::MAX_LOCKS * (8 + 8))
+        + (4 + VaultState::MAX_GUARDIANS * 32)
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8
+        + (4 + VaultState::MAX_TOKEN_MINTS * TokenLimit::SIZE)
+        + 8
+        + 4
+        + (1 + RateLimit::SIZE)
+        + (1 + (1 + RateLimit::SIZE) + 8)
+        + 8
+        + 8
+        + (1 + 32)
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 4
+        + 4
+        + (4 + VaultState::MAX_RECOVERY_KEYS * 32)
+        + 8
+        + (1 + 32 + 8)
+        + 8
+        + 8
+        + 8;
+}
+
+impl VaultState {
+    pub const MAX_TOKEN_MINTS: usize = 8;
+    pub const MAX_LOCKS: usize = 8;
+    pub const MAX_GUARDIANS: usize = 5;
+    pub const MAX_RECOVERY_KEYS: usize = 3;
+    /// Seconds before a change to an active rate limit takes effect.
+    pub const RATE_LIMIT_DELAY: i64 = 24 * 60 * 60;
+    /// Seconds over which `withdraw_limit` caps lamports paid out without
+    /// guardian approval.
+    pub const APPROVAL_WINDOW: i64 = 24 * 60 * 60;
+
+    pub fn is_unlocked(&self, now: i64) -> bool {
+        match self.unlock_at {
+            Some(unlock_at) => unlock_at <= now,
+            None => true,
+        }
+    }
+
+    pub fn locked_amount(&self, now: i64) -> u64 {
+        self.locks
+            .iter()
+            .filter(|lock| lock.unlock_at > now)
+            .map(|lock| lock.amount)
+            .sum()
+    }
+
+    pub fn prune_locks(&mut self, now: i64) {
+        self.locks.retain(|lock| lock.unlock_at > now);
+    }
+
+    pub fn ensure_withdrawable(&self, amount: u64, balance: u64, now: i64) -> Result<()> {
+        require!(self.is_unlocked(now), VaultError::VaultLocked);
+        let available = balance
+            .saturating_sub(self.locked_amount(now))
+            .saturating_sub(self.committed);
+        require!(amount <= available, VaultError::FundsLocked);
+
+        // The system program refuses to leave a funded account below rent exemption
+        let remaining = balance - amount;
+        let rent = Rent::get()?.minimum_balance(0);
+        require!(
+            remaining == 0 || remaining >= rent,
+            VaultError::BelowRentExemption
+        );
+        // Open streams are paid out of what remains, so it must stay rent
+        // exempt once they have settled
+        if self.committed > 0 {
+            require!(
+                remaining - self.committed >= rent,
+                VaultError::BelowRentExemption
+            );
+        }
+        Ok(())
+    }
+
+    /// Like `ensure_withdrawable`, but a committed stream is paid out over
+    /// time, so it can never take the vault below the rent minimum.
+    pub fn ensure_committable(&self, amount: u64, balance: u64, now: i64) -> Result<()> {
+        self.ensure_withdrawable(amount, balance, now)?;
+        require!(
+            balance - amount - self.committed >= Rent::get()?.minimum_balance(0),
+            VaultError::BelowRentExemption
+        );
+        Ok(())
+    }
+
+    pub fn apply_pending_rate_limit(&mut self, now: i64) {
+        if let Some(pending) = self.pending_rate_limit {
+            if pending.effective_at <= now {
+                self.rate_limit = pending.limit;
+                self.pending_rate_limit = None;
+                self.window_start = 0;
+                self.window_withdrawn = 0;
+            }
+        }
+    }
+
+    /// Counts `amount` against the current window, rolling it over once it has elapsed.
+    pub fn consume_rate_limit(&mut self, amount: u64, clock: &Clock) -> Result<()> {
+        self.apply_pending_rate_limit(clock.unix_timestamp);
+        let Some(limit) = self.rate_limit else {
+            return Ok(());
+        };
+
+        let now = limit.now(clock);
+        if now.saturating_sub(self.window_start) >= limit.window {
+            self.window_start = now;
+            self.window_withdrawn = 0;
+        }
+
+        let withdrawn = self
+            .window_withdrawn
+            .checked_add(amount)
+            .ok_or(VaultError::RateLimitExceeded)?;
+        require!(withdrawn <= limit.max_amount, VaultError::RateLimitExceeded);
+        self.window_withdrawn = withdrawn;
+        Ok(())
+    }
+
+    /// Counts `amount` against what a guarded vault may pay out without
+    /// guardian approval; anything above it must go through a
+    /// `WithdrawalProposal`.
+    pub fn consume_withdraw_limit(&mut self, amount: u64, now: i64) -> Result<()> {
+        if !self.guarded() {
+            return Ok(());
+        }
+
+        if now.saturating_sub(self.approval_window_start) >= Self::APPROVAL_WINDOW {
+            self.approval_window_start = now;
+            self.approval_window_withdrawn = 0;
+        }
+
+        let withdrawn = self
+            .approval_window_withdrawn
+            .checked_add(amount)
+            .ok_or(VaultError::ApprovalRequired)?;
+        require!(
+            withdrawn <= self.withdraw_limit,
+            VaultError::ApprovalRequired
+        );
+        self.approval_window_withdrawn = withdrawn;
+        Ok(())
+    }
+
+    /// Counts `amount` of `mint` against its entry in `token_limits`,
+    /// returning whether it fits. Nothing is counted if it does not.
+    pub fn consume_token_limit(&mut self, mint: &Pubkey, amount: u64, now: i64) -> bool {
+        let Some(limit) = self.token_limits.iter_mut().find(|limit| limit.mint == *mint) else {
+            return false;
+        };
+
+        if now.saturating_sub(limit.window_start) >= Self::APPROVAL_WINDOW {
+            limit.window_start = now;
+            limit.withdrawn = 0;
+        }
+
+        match limit.withdrawn.checked_add(amount) {
+            Some(withdrawn) if withdrawn <= limit.withdraw_limit => {
+                limit.withdrawn = withdrawn;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_claimable(&self, now: i64) -> bool {
+        self.beneficiary.is_some() && now >= self.last_active.saturating_add(self.inactivity_period)
+    }
+
+    /// The authority, or the beneficiary once the vault is claimable, may
+    /// wind down what would otherwise block a claim.
+    pub fn ensure_can_wind_down(&self, signer: &Pubkey, now: i64) -> Result<()> {
+        if *signer == self.authority {
+            return Ok(());
+        }
+        require!(self.beneficiary == Some(*signer), VaultError::Unauthorized);
+        require!(self.is_claimable(now), VaultError::OwnerStillActive);
+        Ok(())
+    }
+
+    pub fn guarded(&self) -> bool {
+        !self.guardians.is_empty()
+    }
+
+    /// Counts the distinct current guardians among `approvers`.
+    pub fn guardian_approvals<'a>(&self, approvers: impl Iterator<Item = &'a Pubkey>) -> usize {
+        let mut seen: Vec<&Pubkey> = Vec::with_capacity(self.guardians.len());
+        for approver in approvers {
+            if self.guardians.contains(approver) && !seen.contains(&approver) {
+                seen.push(approver);
+            }
+        }
+        seen.len()
+    }
+
+    /// Requires `threshold` guardians to co-sign as remaining accounts, if
+    /// the vault is guarded.
+    pub fn require_guardian_quorum(&self, remaining_accounts: &[AccountInfo]) -> Result<()> {
+        if !self.guarded() {
+            return Ok(());
+        }
+
+        let approvals = self.guardian_approvals(
+            remaining_accounts
+                .iter()
+                .filter(|account| account.is_signer)
+                .map(|account| account.key),
+        );
+        require!(
+            approvals >= self.threshold as usize,
+            VaultError::ThresholdNotMet
+        );
+        Ok(())
+    }
+}
+
+#[account]
+pub struct WithdrawalProposal {
+    pub vault_state: Pubkey,
+    pub amount: u64,
+    pub approvals: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl Space for WithdrawalProposal {
+    const INIT_SPACE: usize = 8 + 32 + 8 + (4 + VaultState::MAX_GUARDIANS * 32) + 1;
+}
+
+/// Index of the vault ids a user has open.
+#[account]
+pub struct UserVaults {
+    pub vault_ids: Vec<u64>,
+    pub bump: u8,
+}
+
+impl Space for UserVaults {
+    const INIT_SPACE: usize = 8 + (4 + UserVaults::MAX_VAULTS * 8) + 1;
+}
+
+impl UserVaults {
+    pub const MAX_VAULTS: usize = 16;
+
+    pub fn remove(&mut self, vault_id: u64) {
+        self.vault_ids.retain(|id| *id != vault_id);
+    }
+}
+
+/// A delegate's right to spend lamports from a vault. `owner` and
+/// `vault_state` lead the account data so allowances can be listed with a
+/// memcmp filter at offset 8 or 40.
+#[account]
+pub struct Allowance {
+    pub owner: Pubkey,
+    pub vault_state: Pubkey,
+    pub delegate: Pubkey,
+    pub max_amount: u64,
+    pub spent: u64,
+    pub expires_at: Option<i64>,
+    pub period: Option<SpendPeriod>,
+    pub period_start: i64,
+    pub period_spent: u64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct SpendPeriod {
+    /// Period length in seconds.
+    pub length: i64,
+    pub cap: u64,
+}
+
+impl Space for Allowance {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + (1 + 8) + (1 + 8 + 8) + 8 + 8 + 1;
+}
+
+impl Allowance {
+    pub fn consume(&mut self, amount: u64, now: i64) -> Result<()> {
+        if let Some(expires_at) = self.expires_at {
+            require!(now < expires_at, VaultError::AllowanceExpired);
+        }
+
+        let spent = self
+            .spent
+            .checked_add(amount)
+            .ok_or(VaultError::AllowanceExceeded)?;
+        require!(spent <= self.max_amount, VaultError::AllowanceExceeded);
+
+        if let Some(period) = self.period {
+            if now.saturating_sub(self.period_start) >= period.length {
+                self.period_start = now;
+                self.period_spent = 0;
+            }
+            let period_spent = self
+                .period_spent
+                .checked_add(amount)
+                .ok_or(VaultError::AllowanceExceeded)?;
+            require!(period_spent <= period.cap, VaultError::AllowanceExceeded);
+            self.period_spent = period_spent;
+        }
+
+        self.spent = spent;
+        Ok(())
+    }
+}
+
+/// Linear payment of `rate_per_second` lamports from `start` to `end`.
+#[account]
+pub struct Stream {
+    pub owner: Pubkey,
+    pub vault_state: Pubkey,
+    pub recipient: Pubkey,
+    pub id: u64,
+    pub rate_per_second: u64,
+    pub start: i64,
+    pub end: i64,
+    pub withdrawn: u64,
+    pub bump: u8,
+}
+
+impl Space for Stream {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+impl Stream {
+    pub fn total(&self) -> Option<u64> {
+        self.rate_per_second
+            .checked_mul(self.end.checked_sub(self.start)? as u64)
+    }
+
+    pub fn vested(&self, now: i64) -> u64 {
+        let elapsed = now.clamp(self.start, self.end) - self.start;
+        // Bounded by `total`, which is checked when the stream is created
+        self.rate_per_second * elapsed as u64
+    }
+
+    pub fn withdrawable(&self, now: i64) -> u64 {
+        self.vested(now).saturating_sub(self.withdrawn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_state(rate_limit: Option<RateLimit>) -> VaultState {
+        VaultState {
+            owner: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            vault_id: 0,
+            vault_bump: 0,
+            state_bump: 0,
+            token_mints: vec![],
+            unlock_at: None,
+            locks: vec![],
+            guardians: vec![],
+            threshold: 0,
+            withdraw_limit: 0,
+            approval_window_start: 0,
+            approval_window_withdrawn: 0,
+            token_limits: vec![],
+            proposal_count: 0,
+            open_proposals: 0,
+            rate_limit,
+            pending_rate_limit: None,
+            window_start: 0,
+            window_withdrawn: 0,
+            beneficiary: None,
+            inactivity_period: 0,
+            last_active: 0,
+            stream_count: 0,
+            committed: 0,
+            stake_count: 0,
+            stake_accounts: 0,
+            allowances: 0,
+            recovery_keys: vec![],
+            recovery_delay: 0,
+            pending_recovery: None,
+            total_deposited: 0,
+            total_withdrawn: 0,
+            created_at: 0,
+        }
+    }
+
+    fn clock(slot: u64, unix_timestamp: i64) -> Clock {
+        Clock {
+            slot,
+            unix_timestamp,
+            ..Clock::default()
+        }
+    }
+
+    fn limit(max_amount: u64, window: u64, unit: WindowUnit) -> RateLimit {
+        RateLimit {
+            max_amount,
+            window,
+            unit,
+        }
+    }
+
+    #[test]
+    fn rate_limit_caps_a_window() {
+        let mut state = vault_state(Some(limit(100, 10, WindowUnit::Seconds)));
+        state.consume_rate_limit(60, &clock(0, 1_000)).unwrap();
+        state.consume_rate_limit(40, &clock(0, 1_005)).unwrap();
+        assert!(state.consume_rate_limit(1, &clock(0, 1_009)).is_err());
+        assert_eq!(state.window_withdrawn, 100);
+    }
+
+    #[test]
+    fn rate_limit_window_rolls_over() {
+        let mut state = vault_state(Some(limit(100, 10, WindowUnit::Seconds)));
+        state.consume_rate_limit(100, &clock(0, 1_000)).unwrap();
+        state.consume_rate_limit(100, &clock(0, 1_010)).unwrap();
+        assert_eq!(state.window_start, 1_010);
+        assert_eq!(state.window_withdrawn, 100);
+    }
+
+    #[test]
+    fn rate_limit_window_unit_picks_the_clock_field() {
+        let mut by_slot = vault_state(Some(limit(100, 10, WindowUnit::Slots)));
+        by_slot.consume_rate_limit(100, &clock(50, 0)).unwrap();
+        // Seconds moved on but slots did not, so the window is still full
+        assert!(by_slot.consume_rate_limit(1, &clock(59, 1_000)).is_err());
+        by_slot.consume_rate_limit(100, &clock(60, 1_000)).unwrap();
+        assert_eq!(by_slot.window_start, 60);
+
+        let mut by_second = vault_state(Some(limit(100, 10, WindowUnit::Seconds)));
+        by_second.consume_rate_limit(100, &clock(0, 50)).unwrap();
+        assert!(by_second.consume_rate_limit(1, &clock(1_000, 59)).is_err());
+        by_second
+            .consume_rate_limit(100, &clock(1_000, 60))
+            .unwrap();
+        assert_eq!(by_second.window_start, 60);
+    }
+
+    #[test]
+    fn rate_limit_is_optional() {
+        let mut state = vault_state(None);
+        state.consume_rate_limit(u64::MAX, &clock(0, 0)).unwrap();
+        assert_eq!(state.window_withdrawn, 0);
+    }
+
+    #[test]
+    fn pending_rate_limit_waits_for_its_delay() {
+        let mut state = vault_state(Some(limit(100, 10_000, WindowUnit::Seconds)));
+        state.pending_rate_limit = Some(PendingRateLimit {
+            limit: Some(limit(1_000, 10_000, WindowUnit::Seconds)),
+            effective_at: 2_000,
+        });
+
+        state.consume_rate_limit(100, &clock(0, 1_000)).unwrap();
+        assert!(state.consume_rate_limit(1, &clock(0, 1_999)).is_err());
+        assert!(state.pending_rate_limit.is_some());
+
+        // Applying resets the window, so the new cap is available at once
+        state.consume_rate_limit(1_000, &clock(0, 2_000)).unwrap();
+        assert!(state.pending_rate_limit.is_none());
+        assert_eq!(state.rate_limit.unwrap().max_amount, 1_000);
+    }
+
+    #[test]
+    fn pending_removal_lifts_the_limit() {
+        let mut state = vault_state(Some(limit(100, 10, WindowUnit::Seconds)));
+        state.pending_rate_limit = Some(PendingRateLimit {
+            limit: None,
+            effective_at: 2_000,
+        });
+        state.apply_pending_rate_limit(2_000);
+        assert!(state.rate_limit.is_none());
+        state
+            .consume_rate_limit(u64::MAX, &clock(0, 2_000))
+            .unwrap();
+    }
+
+    fn guarded(withdraw_limit: u64) -> VaultState {
+        let mut state = vault_state(None);
+        state.guardians = vec![Pubkey::new_unique()];
+        state.threshold = 1;
+        state.withdraw_limit = withdraw_limit;
+        state
+    }
+
+    #[test]
+    fn withdraw_limit_caps_unapproved_outflow_per_window() {
+        let start = 1_000_000;
+        let mut state = guarded(100);
+        state.consume_withdraw_limit(60, start).unwrap();
+        assert_eq!(state.approval_window_start, start);
+        // Splitting a withdrawal does not get around the limit
+        assert!(state.consume_withdraw_limit(60, start + 1).is_err());
+        state.consume_withdraw_limit(40, start + 1).unwrap();
+
+        let rollover = start + VaultState::APPROVAL_WINDOW;
+        assert!(state.consume_withdraw_limit(1, rollover - 1).is_err());
+        state.consume_withdraw_limit(100, rollover).unwrap();
+        assert_eq!(state.approval_window_start, rollover);
+    }
+
+    #[test]
+    fn withdraw_limit_only_applies_to_guarded_vaults() {
+        let mut state = vault_state(None);
+        state.consume_withdraw_limit(u64::MAX, 0).unwrap();
+        assert_eq!(state.approval_window_withdrawn, 0);
+    }
+
+    fn token_limit(mint: Pubkey, withdraw_limit: u64) -> TokenLimit {
+        TokenLimit {
+            mint,
+            withdraw_limit,
+            window_start: 0,
+            withdrawn: 0,
+        }
+    }
+
+    #[test]
+    fn token_limit_caps_each_mint_per_window() {
+        let start = 1_000_000;
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut state = guarded(1_000_000_000);
+        state.token_limits = vec![token_limit(a, 100), token_limit(b, 10)];
+
+        assert!(state.consume_token_limit(&a, 60, start));
+        assert!(!state.consume_token_limit(&a, 60, start + 1));
+        assert!(state.consume_token_limit(&a, 40, start + 1));
+        // Each mint has its own budget, separate from the lamport limit
+        assert!(state.consume_token_limit(&b, 10, start + 1));
+        assert!(!state.consume_token_limit(&b, 1, start + 1));
+        assert_eq!(state.approval_window_withdrawn, 0);
+
+        let rollover = start + VaultState::APPROVAL_WINDOW;
+        assert!(!state.consume_token_limit(&a, 1, rollover - 1));
+        assert!(state.consume_token_limit(&a, 100, rollover));
+        assert_eq!(state.token_limits[0].window_start, rollover);
+    }
+
+    #[test]
+    fn token_limit_is_zero_for_mints_without_one() {
+        let mut state = guarded(u64::MAX);
+        assert!(!state.consume_token_limit(&Pubkey::new_unique(), 1, 0));
+    }
+
+    fn stream(rate_per_second: u64, start: i64, end: i64) -> Stream {
+        Stream {
+            owner: Pubkey::new_unique(),
+            vault_state: Pubkey::new_unique(),
+            recipient: Pubkey::new_unique(),
+            id: 0,
+            rate_per_second,
+            start,
+            end,
+            withdrawn: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn stream_vests_linearly_between_start_and_end() {
+        let stream = stream(10, 100, 200);
+        assert_eq!(stream.total(), Some(1_000));
+        assert_eq!(stream.vested(0), 0);
+        assert_eq!(stream.vested(100), 0);
+        assert_eq!(stream.vested(150), 500);
+        assert_eq!(stream.vested(200), 1_000);
+        assert_eq!(stream.vested(10_000), 1_000);
+    }
+
+    #[test]
+    fn stream_withdrawable_excludes_withdrawn() {
+        let mut stream = stream(10, 100, 200);
+        stream.withdrawn = 300;
+        assert_eq!(stream.withdrawable(150), 200);
+        assert_eq!(stream.withdrawable(120), 0);
+        assert_eq!(stream.withdrawable(300), 700);
+    }
+
+    fn allowance(
+        max_amount: u64,
+        expires_at: Option<i64>,
+        period: Option<SpendPeriod>,
+    ) -> Allowance {
+        Allowance {
+            owner: Pubkey::new_unique(),
+            vault_state: Pubkey::new_unique(),
+            delegate: Pubkey::new_unique(),
+            max_amount,
+            spent: 0,
+            expires_at,
+            period,
+            period_start: 1_000,
+            period_spent: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn allowance_expires() {
+        let mut allowance = allowance(100, Some(2_000), None);
+        allowance.consume(10, 1_999).unwrap();
+        assert!(allowance.consume(10, 2_000).is_err());
+        assert_eq!(allowance.spent, 10);
+    }
+
+    #[test]
+    fn allowance_caps_the_total() {
+        let mut allowance = allowance(100, None, None);
+        allowance.consume(70, 1_000).unwrap();
+        assert!(allowance.consume(31, 1_000).is_err());
+        allowance.consume(30, 5_000).unwrap();
+        assert!(allowance.consume(1, 1_000_000).is_err());
+        assert_eq!(allowance.spent, 100);
+    }
+
+    #[test]
+    fn allowance_period_rolls_over() {
+        let period = SpendPeriod {
+            length: 100,
+            cap: 40,
+        };
+        let mut allowance = allowance(100, None, Some(period));
+        allowance.consume(40, 1_000).unwrap();
+        assert!(allowance.consume(1, 1_099).is_err());
+
+        allowance.consume(40, 1_100).unwrap();
+        assert_eq!(allowance.period_start, 1_100);
+        assert_eq!(allowance.period_spent, 40);
+
+        // The total still applies across periods
+        assert!(allowance.consume(21, 1_200).is_err());
+        allowance.consume(20, 1_200).unwrap();
+        assert_eq!(allowance.spent, 100);
+    }
+}
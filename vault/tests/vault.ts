@@ -1,5 +1,22 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
+import {
+  createAssociatedTokenAccount,
+  createMint,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+} from "@solana/spl-token";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  StakeProgram,
+} from "@solana/web3.js";
+import { expect } from "chai";
 import { Vault } from "../target/types/vault";
 
 describe("vault", () => {
@@ -7,10 +24,871 @@ describe("vault", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
 
   const program = anchor.workspace.vault as Program<Vault>;
+  const provider = anchor.getProvider() as anchor.AnchorProvider;
+  const user = provider.wallet.publicKey;
+
+  const statePda = (vaultId: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("state"),
+        user.toBuffer(),
+        vaultId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  const stakePda = (vaultState: PublicKey, stakeId: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("stake"),
+        vaultState.toBuffer(),
+        stakeId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    try {
+      await promise;
+      expect.fail(`expected ${code}`);
+    } catch (err) {
+      expect(err.error?.errorCode?.code).to.equal(code);
+    }
+  };
+
+  const sleep = (ms: number) =>
+    new Promise((resolve) => setTimeout(resolve, ms));
+
+  const now = () => Math.floor(Date.now() / 1000);
+
+  const sol = (amount: number) => new anchor.BN(amount * LAMPORTS_PER_SOL);
+
+  const open = async (vaultId: anchor.BN, deposit: number) => {
+    await program.methods.initialize(vaultId, null).rpc();
+    await program.methods
+      .deposit(vaultId, sol(deposit))
+      .accountsPartial({ vaultState: statePda(vaultId) })
+      .rpc();
+  };
+
+  const withdraw = (vaultId: anchor.BN, amount: number) =>
+    program.methods
+      .withdraw(vaultId, sol(amount))
+      .accountsPartial({ vaultState: statePda(vaultId) })
+      .rpc();
+
+  const validatorVote = async () => {
+    const { current } = await provider.connection.getVoteAccounts();
+    return new PublicKey(current[0].votePubkey);
+  };
 
   it("Is initialized!", async () => {
     // Add your test here.
-    const tx = await program.methods.initialize().rpc();
+    const tx = await program.methods.initialize(new anchor.BN(0), null).rpc();
     console.log("Your transaction signature", tx);
   });
+
+  it("Delegates vault lamports to the local validator", async () => {
+    const vaultId = new anchor.BN(1);
+    const vaultState = statePda(vaultId);
+    const stakeAccount = stakePda(vaultState, new anchor.BN(0));
+
+    await program.methods.initialize(vaultId, null).rpc();
+    await program.methods
+      .deposit(vaultId, new anchor.BN(3 * LAMPORTS_PER_SOL))
+      .accountsPartial({ vaultState })
+      .rpc();
+    await program.methods
+      .delegateStake(vaultId, new anchor.BN(2 * LAMPORTS_PER_SOL))
+      .accountsPartial({
+        vaultState,
+        stakeAccount,
+        validatorVote: await validatorVote(),
+      })
+      .rpc();
+
+    const stake = await provider.connection.getAccountInfo(stakeAccount);
+    expect(stake.owner.toBase58()).to.equal(StakeProgram.programId.toBase58());
+    expect(stake.lamports).to.equal(2 * LAMPORTS_PER_SOL);
+
+    await program.methods
+      .unstake(vaultId, new anchor.BN(0))
+      .accountsPartial({ vaultState, stakeAccount })
+      .rpc();
+
+    let state = await program.account.vaultState.fetch(vaultState);
+    expect(state.stakeAccounts).to.equal(1);
+
+    // Deactivated in the epoch it was delegated, so it never warmed up and
+    // can be withdrawn straight away
+    await program.methods
+      .withdrawStake(vaultId, new anchor.BN(0))
+      .accountsPartial({ vaultState, stakeAccount })
+      .rpc();
+
+    state = await program.account.vaultState.fetch(vaultState);
+    expect(state.stakeAccounts).to.equal(0);
+    expect(await provider.connection.getAccountInfo(stakeAccount)).to.be.null;
+  });
+
+  it("Refuses to stake lamports that are still locked", async () => {
+    const vaultId = new anchor.BN(2);
+    const vaultState = statePda(vaultId);
+    const unlockAt = Math.floor(Date.now() / 1000) + 3600;
+
+    await program.methods.initialize(vaultId, null).rpc();
+    await program.methods
+      .depositLocked(
+        vaultId,
+        new anchor.BN(2 * LAMPORTS_PER_SOL),
+        new anchor.BN(unlockAt)
+      )
+      .accountsPartial({ vaultState })
+      .rpc();
+
+    await expectError(
+      program.methods
+        .delegateStake(vaultId, new anchor.BN(LAMPORTS_PER_SOL))
+        .accountsPartial({
+          vaultState,
+          stakeAccount: stakePda(vaultState, new anchor.BN(0)),
+          validatorVote: await validatorVote(),
+        })
+        .rpc(),
+      "FundsLocked"
+    );
+  });
+
+  describe("time locks", () => {
+    const vaultId = new anchor.BN(3);
+
+    it("Refuses withdrawals before the vault unlocks", async () => {
+      const unlockAt = now() + 3;
+      await program.methods.initialize(vaultId, new anchor.BN(unlockAt)).rpc();
+      await program.methods
+        .deposit(vaultId, sol(1))
+        .accountsPartial({ vaultState: statePda(vaultId) })
+        .rpc();
+
+      await expectError(withdraw(vaultId, 0.5), "VaultLocked");
+      // A lock can only move later
+      await expectError(
+        program.methods
+          .extendLock(vaultId, new anchor.BN(unlockAt - 1))
+          .accountsPartial({ vaultState: statePda(vaultId) })
+          .rpc(),
+        "InvalidUnlockTime"
+      );
+    });
+
+    it("Releases funds once the lock has passed", async () => {
+      await sleep(5_000);
+      await withdraw(vaultId, 0.5);
+
+      const state = await program.account.vaultState.fetch(statePda(vaultId));
+      expect(state.totalWithdrawn.toNumber()).to.equal(0.5 * LAMPORTS_PER_SOL);
+    });
+
+    it("Keeps locked deposits out of withdrawals", async () => {
+      const lockedId = new anchor.BN(4);
+      await open(lockedId, 1);
+      await program.methods
+        .depositLocked(lockedId, sol(1), new anchor.BN(now() + 3600))
+        .accountsPartial({ vaultState: statePda(lockedId) })
+        .rpc();
+
+      await expectError(withdraw(lockedId, 1.5), "FundsLocked");
+      await withdraw(lockedId, 0.5);
+    });
+  });
+
+  describe("guardians", () => {
+    const vaultId = new anchor.BN(5);
+    const vaultState = statePda(vaultId);
+    const guardians = [Keypair.generate(), Keypair.generate()];
+
+    const proposalPda = (id: number) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("proposal"),
+          vaultState.toBuffer(),
+          new anchor.BN(id).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const propose = async (amount: number) => {
+      const { proposalCount } = await program.account.vaultState.fetch(
+        vaultState
+      );
+      const proposal = proposalPda(proposalCount.toNumber());
+      await program.methods
+        .proposeWithdrawal(vaultId, sol(amount))
+        .accountsPartial({ vaultState, proposal })
+        .rpc();
+      return proposal;
+    };
+
+    const approve = (proposal: PublicKey, guardian: Keypair) =>
+      program.methods
+        .approveWithdrawal()
+        .accountsPartial({ guardian: guardian.publicKey, vaultState, proposal })
+        .signers([guardian])
+        .rpc();
+
+    const execute = (proposal: PublicKey) =>
+      program.methods
+        .executeWithdrawal(vaultId)
+        .accountsPartial({ vaultState, proposal })
+        .rpc();
+
+    const coSigners = (keys: Keypair[]) =>
+      keys.map((key) => ({
+        pubkey: key.publicKey,
+        isSigner: true,
+        isWritable: false,
+      }));
+
+    before(async () => {
+      await open(vaultId, 3);
+      await program.methods
+        .setGuardians(
+          vaultId,
+          guardians.map((guardian) => guardian.publicKey),
+          2,
+          sol(0.5)
+        )
+        .accountsPartial({ vaultState })
+        .rpc();
+    });
+
+    it("Lets the owner alone withdraw within the limit", async () => {
+      await withdraw(vaultId, 0.2);
+      await expectError(withdraw(vaultId, 0.4), "ApprovalRequired");
+    });
+
+    it("Pays out a proposal once enough guardians approve", async () => {
+      const proposal = await propose(1);
+      await expectError(approve(proposal, Keypair.generate()), "NotGuardian");
+
+      await approve(proposal, guardians[0]);
+      await expectError(approve(proposal, guardians[0]), "AlreadyApproved");
+      await expectError(execute(proposal), "ThresholdNotMet");
+
+      await approve(proposal, guardians[1]);
+      const before = await provider.connection.getBalance(user);
+      await execute(proposal);
+      expect(await provider.connection.getBalance(user)).to.be.greaterThan(
+        before
+      );
+      expect(await provider.connection.getAccountInfo(proposal)).to.be.null;
+    });
+
+    it("Drops approvals from guardians who were rotated out", async () => {
+      const proposal = await propose(0.5);
+      await approve(proposal, guardians[0]);
+      await approve(proposal, guardians[1]);
+
+      const rotated = Array.from({ length: 5 }, () => Keypair.generate());
+      await program.methods
+        .setGuardians(
+          vaultId,
+          rotated.map((guardian) => guardian.publicKey),
+          4,
+          sol(0.5)
+        )
+        .accountsPartial({ vaultState })
+        .remainingAccounts(coSigners(guardians))
+        .signers(guardians)
+        .rpc();
+
+      // Six approvals would not fit if the stale two were kept
+      for (const guardian of rotated.slice(0, 4)) {
+        await approve(proposal, guardian);
+      }
+      const { approvals } = await program.account.withdrawalProposal.fetch(
+        proposal
+      );
+      expect(approvals).to.have.length(4);
+      await execute(proposal);
+    });
+  });
+
+  describe("rate limits", () => {
+    const vaultId = new anchor.BN(6);
+    const vaultState = statePda(vaultId);
+
+    const setRateLimit = (maxAmount: number, window: number) =>
+      program.methods
+        .setRateLimit(vaultId, {
+          maxAmount: sol(maxAmount),
+          window: new anchor.BN(window),
+          unit: { seconds: {} },
+        })
+        .accountsPartial({ vaultState })
+        .rpc();
+
+    before(async () => {
+      await open(vaultId, 3);
+    });
+
+    it("Rejects an empty window", async () => {
+      await expectError(setRateLimit(1, 0), "InvalidRateLimit");
+    });
+
+    it("Caps what leaves the vault per window", async () => {
+      await setRateLimit(1, 3600);
+      await withdraw(vaultId, 0.6);
+      await expectError(withdraw(vaultId, 0.6), "RateLimitExceeded");
+      await withdraw(vaultId, 0.4);
+    });
+
+    it("Delays raising an active limit", async () => {
+      await setRateLimit(2, 3600);
+      await expectError(withdraw(vaultId, 0.1), "RateLimitExceeded");
+
+      const state = await program.account.vaultState.fetch(vaultState);
+      expect(state.rateLimit.maxAmount.toNumber()).to.equal(LAMPORTS_PER_SOL);
+      expect(state.pendingRateLimit.limit.maxAmount.toNumber()).to.equal(
+        2 * LAMPORTS_PER_SOL
+      );
+    });
+  });
+
+  describe("beneficiary", () => {
+    const beneficiary = Keypair.generate();
+
+    const setBeneficiary = (vaultId: anchor.BN, inactivityPeriod: number) =>
+      program.methods
+        .setBeneficiary(
+          vaultId,
+          beneficiary.publicKey,
+          new anchor.BN(inactivityPeriod)
+        )
+        .accountsPartial({ vaultState: statePda(vaultId) })
+        .rpc();
+
+    const claim = (vaultId: anchor.BN, signer = beneficiary) =>
+      program.methods
+        .claim(vaultId)
+        .accountsPartial({
+          beneficiary: signer.publicKey,
+          owner: user,
+          vaultState: statePda(vaultId),
+        })
+        .signers([signer])
+        .rpc();
+
+    it("Waits out the owner's inactivity period", async () => {
+      const vaultId = new anchor.BN(7);
+      await open(vaultId, 1);
+      await expectError(setBeneficiary(vaultId, 0), "InvalidInactivityPeriod");
+      await setBeneficiary(vaultId, 3600);
+
+      await expectError(claim(vaultId), "OwnerStillActive");
+      await expectError(claim(vaultId, Keypair.generate()), "NotBeneficiary");
+    });
+
+    it("Hands the vault to the beneficiary once the owner goes quiet", async () => {
+      const vaultId = new anchor.BN(8);
+      await open(vaultId, 1);
+      await setBeneficiary(vaultId, 1);
+
+      await sleep(3_000);
+      await claim(vaultId);
+
+      expect(
+        await provider.connection.getBalance(beneficiary.publicKey)
+      ).to.be.at.least(LAMPORTS_PER_SOL);
+      expect(await provider.connection.getAccountInfo(statePda(vaultId))).to.be
+        .null;
+    });
+  });
+
+  describe("allowances", () => {
+    const vaultId = new anchor.BN(9);
+    const vaultState = statePda(vaultId);
+    const delegate = Keypair.generate();
+
+    const allowance = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("allowance"),
+        vaultState.toBuffer(),
+        delegate.publicKey.toBuffer(),
+      ],
+      program.programId
+    )[0];
+
+    const spend = (amount: number) =>
+      program.methods
+        .spend(vaultId, sol(amount))
+        .accountsPartial({
+          delegate: delegate.publicKey,
+          owner: user,
+          vaultState,
+          allowance,
+        })
+        .signers([delegate])
+        .rpc();
+
+    before(async () => {
+      await open(vaultId, 2);
+    });
+
+    it("Lets a delegate spend up to its allowance", async () => {
+      await program.methods
+        .approveAllowance(vaultId, delegate.publicKey, sol(0.5), null, null)
+        .accountsPartial({ vaultState, allowance })
+        .rpc();
+
+      await spend(0.3);
+      await expectError(spend(0.3), "AllowanceExceeded");
+      expect(
+        await provider.connection.getBalance(delegate.publicKey)
+      ).to.equal(0.3 * LAMPORTS_PER_SOL);
+    });
+
+    it("Keeps the vault open until the allowance is revoked", async () => {
+      const close = () =>
+        program.methods.close(vaultId).accountsPartial({ vaultState }).rpc();
+      await expectError(close(), "AllowancesOpen");
+
+      await program.methods
+        .revokeAllowance(vaultId)
+        .accountsPartial({ vaultState, allowance })
+        .rpc();
+      await expectError(spend(0.1), "AccountNotInitialized");
+      await close();
+    });
+  });
+
+  describe("streams", () => {
+    const vaultId = new anchor.BN(10);
+    const vaultState = statePda(vaultId);
+    const recipient = Keypair.generate();
+
+    const streamPda = (id: number) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("stream"),
+          vaultState.toBuffer(),
+          new anchor.BN(id).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+    const stream = streamPda(0);
+
+    const createStream = (
+      ratePerSecond: number,
+      start: number,
+      end: number,
+      pda = stream
+    ) =>
+      program.methods
+        .createStream(
+          vaultId,
+          recipient.publicKey,
+          new anchor.BN(ratePerSecond),
+          new anchor.BN(start),
+          new anchor.BN(end)
+        )
+        .accountsPartial({ vaultState, stream: pda })
+        .rpc();
+
+    const withdrawStream = (pda: PublicKey, authority: PublicKey) =>
+      program.methods
+        .withdrawStream(vaultId)
+        .accountsPartial({
+          recipient: recipient.publicKey,
+          owner: user,
+          authority,
+          vaultState,
+          stream: pda,
+        })
+        .signers([recipient])
+        .rpc();
+
+    before(async () => {
+      await open(vaultId, 2);
+      await provider.connection.confirmTransaction(
+        await provider.connection.requestAirdrop(
+          recipient.publicKey,
+          LAMPORTS_PER_SOL
+        )
+      );
+    });
+
+    it("Rejects an empty schedule or one the vault cannot cover", async () => {
+      const start = now();
+      await expectError(createStream(1_000, start, start), "InvalidStream");
+      await expectError(
+        createStream(LAMPORTS_PER_SOL, start, start + 3600),
+        "FundsLocked"
+      );
+    });
+
+    it("Pays the recipient as the stream vests", async () => {
+      const start = now();
+      await createStream(100_000, start, start + 3600);
+      const state = await program.account.vaultState.fetch(vaultState);
+      expect(state.committed.toNumber()).to.equal(360_000_000);
+
+      // What is owed to the stream cannot be withdrawn
+      await expectError(withdraw(vaultId, 1.8), "FundsLocked");
+
+      await sleep(2_000);
+      const before = await provider.connection.getBalance(recipient.publicKey);
+      await withdrawStream(stream, user);
+      expect(
+        await provider.connection.getBalance(recipient.publicKey)
+      ).to.be.greaterThan(before);
+    });
+
+    it("Releases the unvested remainder when cancelled", async () => {
+      await program.methods
+        .cancelStream(vaultId)
+        .accountsPartial({
+          recipient: recipient.publicKey,
+          vaultState,
+          stream,
+        })
+        .rpc();
+
+      const state = await program.account.vaultState.fetch(vaultState);
+      expect(state.committed.toNumber()).to.equal(0);
+      expect(await provider.connection.getAccountInfo(stream)).to.be.null;
+    });
+
+    it("Returns a finished stream's rent to the current authority", async () => {
+      const finished = streamPda(1);
+      const start = now();
+      await createStream(100_000, start, start + 2, finished);
+      const rent = await provider.connection.getBalance(finished);
+
+      const newAuthority = Keypair.generate();
+      await program.methods
+        .transferAuthority(vaultId, newAuthority.publicKey)
+        .accountsPartial({ vaultState })
+        .rpc();
+
+      await sleep(3_000);
+      await expectError(withdrawStream(finished, user), "Unauthorized");
+      await withdrawStream(finished, newAuthority.publicKey);
+
+      expect(await provider.connection.getAccountInfo(finished)).to.be.null;
+      expect(
+        await provider.connection.getBalance(newAuthority.publicKey)
+      ).to.equal(rent);
+    });
+  });
+
+  describe("recovery", () => {
+    const vaultId = new anchor.BN(11);
+    const vaultState = statePda(vaultId);
+    const recoveryKey = Keypair.generate();
+    const newAuthority = Keypair.generate();
+
+    const initiate = (signer: Keypair) =>
+      program.methods
+        .initiateRecovery(vaultId, newAuthority.publicKey)
+        .accountsPartial({ recoveryKey: signer.publicKey, vaultState })
+        .signers([signer])
+        .rpc();
+
+    const complete = (signer: Keypair) =>
+      program.methods
+        .completeRecovery(vaultId)
+        .accountsPartial({ recoveryKey: signer.publicKey, vaultState })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      await open(vaultId, 1);
+      await program.methods
+        .setRecovery(vaultId, [recoveryKey.publicKey], new anchor.BN(2))
+        .accountsPartial({ vaultState })
+        .rpc();
+    });
+
+    it("Only lets a recovery key start a recovery", async () => {
+      await expectError(initiate(Keypair.generate()), "NotRecoveryKey");
+      await initiate(recoveryKey);
+      await expectError(initiate(recoveryKey), "RecoveryPending");
+    });
+
+    it("Rotates the authority once the delay has passed", async () => {
+      await expectError(complete(recoveryKey), "RecoveryNotReady");
+
+      await sleep(3_000);
+      await complete(recoveryKey);
+
+      const state = await program.account.vaultState.fetch(vaultState);
+      expect(state.authority.toBase58()).to.equal(
+        newAuthority.publicKey.toBase58()
+      );
+      expect(state.owner.toBase58()).to.equal(user.toBase58());
+      // The old authority no longer signs for the vault
+      await expectError(withdraw(vaultId, 0.5), "Unauthorized");
+    });
+  });
+
+  const tokenPrograms: [string, PublicKey, number][] = [
+    ["SPL Token", TOKEN_PROGRAM_ID, 12],
+    ["Token-2022", TOKEN_2022_PROGRAM_ID, 16],
+  ];
+
+  for (const [name, tokenProgram, firstVaultId] of tokenPrograms) {
+    describe(`${name} deposits`, () => {
+      const payer = (provider.wallet as anchor.Wallet).payer;
+      const guardian = Keypair.generate();
+      const beneficiary = Keypair.generate();
+      const vaultIds = Array.from(
+        { length: 4 },
+        (_, i) => new anchor.BN(firstVaultId + i)
+      );
+      let mint: PublicKey;
+
+      const vaultPda = (vaultId: anchor.BN) =>
+        PublicKey.findProgramAddressSync(
+          [
+            Buffer.from("vault"),
+            user.toBuffer(),
+            vaultId.toArrayLike(Buffer, "le", 8),
+          ],
+          program.programId
+        )[0];
+
+      const ata = (owner: PublicKey) =>
+        getAssociatedTokenAddressSync(mint, owner, true, tokenProgram);
+
+      const vaultAta = (vaultId: anchor.BN) => ata(vaultPda(vaultId));
+
+      const balance = async (owner: PublicKey) => {
+        const account = await getAccount(
+          provider.connection,
+          ata(owner),
+          undefined,
+          tokenProgram
+        );
+        return Number(account.amount);
+      };
+
+      const splAccounts = (vaultId: anchor.BN) => ({
+        vaultState: statePda(vaultId),
+        vault: vaultPda(vaultId),
+        mint,
+        userAta: ata(user),
+        vaultAta: vaultAta(vaultId),
+        tokenProgram,
+      });
+
+      const depositSpl = (vaultId: anchor.BN, amount: number) =>
+        program.methods
+          .depositSpl(vaultId, new anchor.BN(amount))
+          .accountsPartial(splAccounts(vaultId))
+          .rpc();
+
+      const coSignerMetas = (keys: Keypair[]) =>
+        keys.map((key) => ({
+          pubkey: key.publicKey,
+          isSigner: true,
+          isWritable: false,
+        }));
+
+      const withdrawSpl = (
+        vaultId: anchor.BN,
+        amount: number,
+        coSigners: Keypair[] = []
+      ) =>
+        program.methods
+          .withdrawSpl(vaultId, new anchor.BN(amount))
+          .accountsPartial(splAccounts(vaultId))
+          .remainingAccounts(coSignerMetas(coSigners))
+          .signers(coSigners)
+          .rpc();
+
+      const close = (vaultId: anchor.BN, tokenAccounts: PublicKey[] = []) =>
+        program.methods
+          .close(vaultId)
+          .accountsPartial({ vaultState: statePda(vaultId) })
+          .remainingAccounts(
+            tokenAccounts.map((pubkey) => ({
+              pubkey,
+              isSigner: false,
+              isWritable: false,
+            }))
+          )
+          .rpc();
+
+      before(async () => {
+        mint = await createMint(
+          provider.connection,
+          payer,
+          user,
+          null,
+          6,
+          Keypair.generate(),
+          undefined,
+          tokenProgram
+        );
+        await createAssociatedTokenAccount(
+          provider.connection,
+          payer,
+          mint,
+          user,
+          undefined,
+          tokenProgram
+        );
+        await mintTo(
+          provider.connection,
+          payer,
+          mint,
+          ata(user),
+          user,
+          10_000,
+          [],
+          undefined,
+          tokenProgram
+        );
+        await provider.connection.confirmTransaction(
+          await provider.connection.requestAirdrop(
+            beneficiary.publicKey,
+            LAMPORTS_PER_SOL
+          )
+        );
+        for (const vaultId of vaultIds) {
+          await open(vaultId, 0.1);
+        }
+      });
+
+      it("Rejects an empty deposit", async () => {
+        await expectError(depositSpl(vaultIds[0], 0), "ZeroAmount");
+      });
+
+      it("Keeps the vault open while its token vault holds a balance", async () => {
+        const vaultId = vaultIds[0];
+        await depositSpl(vaultId, 500);
+        expect(await balance(vaultPda(vaultId))).to.equal(500);
+
+        await expectError(close(vaultId), "TokenVaultsNotEmpty");
+        await withdrawSpl(vaultId, 200);
+        await expectError(close(vaultId), "TokenVaultsNotEmpty");
+      });
+
+      it("Closes the token vault once it is emptied", async () => {
+        const vaultId = vaultIds[0];
+        await withdrawSpl(vaultId, 300);
+
+        expect(await provider.connection.getAccountInfo(vaultAta(vaultId))).to
+          .be.null;
+        const { tokenMints } = await program.account.vaultState.fetch(
+          statePda(vaultId)
+        );
+        expect(tokenMints).to.be.empty;
+        await close(vaultId);
+      });
+
+      it("Refuses to close over tokens sent straight to the vault", async () => {
+        const vaultId = vaultIds[1];
+        const { address } = await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          payer,
+          mint,
+          vaultPda(vaultId),
+          true,
+          undefined,
+          undefined,
+          tokenProgram
+        );
+        await mintTo(
+          provider.connection,
+          payer,
+          mint,
+          address,
+          user,
+          1,
+          [],
+          undefined,
+          tokenProgram
+        );
+
+        await expectError(close(vaultId, [address]), "TokenVaultsNotEmpty");
+        await expectError(close(vaultId, [ata(user)]), "NotVaultTokenAccount");
+      });
+
+      it("Needs guardians above the mint's guardian-free limit", async () => {
+        const vaultId = vaultIds[2];
+        const vaultState = statePda(vaultId);
+        await depositSpl(vaultId, 1_000);
+        await program.methods
+          .setGuardians(vaultId, [guardian.publicKey], 1, sol(1))
+          .accountsPartial({ vaultState })
+          .rpc();
+
+        // Without a limit of its own, no amount of the mint is guardian-free
+        await expectError(withdrawSpl(vaultId, 1), "ThresholdNotMet");
+
+        const setTokenLimit = (coSigners: Keypair[]) =>
+          program.methods
+            .setTokenLimit(vaultId, mint, new anchor.BN(100))
+            .accountsPartial({ vaultState })
+            .remainingAccounts(coSignerMetas(coSigners))
+            .signers(coSigners)
+            .rpc();
+        await expectError(setTokenLimit([]), "ThresholdNotMet");
+        await setTokenLimit([guardian]);
+
+        await withdrawSpl(vaultId, 60);
+        await expectError(withdrawSpl(vaultId, 60), "ThresholdNotMet");
+        await withdrawSpl(vaultId, 40);
+        await withdrawSpl(vaultId, 500, [guardian]);
+        expect(await balance(vaultPda(vaultId))).to.equal(400);
+      });
+
+      it("Hands the token vault to the beneficiary once the owner goes quiet", async () => {
+        const vaultId = vaultIds[3];
+        const vaultState = statePda(vaultId);
+        await depositSpl(vaultId, 400);
+        await program.methods
+          .setBeneficiary(vaultId, beneficiary.publicKey, new anchor.BN(1))
+          .accountsPartial({ vaultState })
+          .rpc();
+
+        const claimSpl = () =>
+          program.methods
+            .claimSpl(vaultId)
+            .accountsPartial({
+              beneficiary: beneficiary.publicKey,
+              owner: user,
+              vaultState,
+              vault: vaultPda(vaultId),
+              mint,
+              beneficiaryAta: ata(beneficiary.publicKey),
+              vaultAta: vaultAta(vaultId),
+              tokenProgram,
+            })
+            .signers([beneficiary])
+            .rpc();
+        const claim = () =>
+          program.methods
+            .claim(vaultId)
+            .accountsPartial({
+              beneficiary: beneficiary.publicKey,
+              owner: user,
+              vaultState,
+            })
+            .signers([beneficiary])
+            .rpc();
+
+        await sleep(3_000);
+        await expectError(claim(), "TokenVaultsNotEmpty");
+
+        await claimSpl();
+        expect(await balance(beneficiary.publicKey)).to.equal(400);
+        expect(await provider.connection.getAccountInfo(vaultAta(vaultId))).to
+          .be.null;
+        await claim();
+      });
+    });
+  }
 });